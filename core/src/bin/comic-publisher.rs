@@ -0,0 +1,7 @@
+use comics::cli::{self, Cli};
+use std::error::Error;
+use structopt::StructOpt;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    cli::run(Cli::from_args())
+}
@@ -0,0 +1,506 @@
+//! Checking a config for problems before anything is built.
+
+use crate::{
+    config::Config,
+    dirs::{join_inside, Dirs},
+    error::{locate_key, BuildError},
+    folders::{ignore_patterns, order_paths, read_folder},
+    site::chapter_url,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Names at the top of the output folder that the build writes itself.
+/// Anything in the static folder is added to these.
+pub(crate) static RESERVED: &[&str] = &["images", "thumbnails", "index.html", "404.html"];
+
+/// Finds where a setting is in the config's text, so problems can point at
+/// it. Looks for the `nth` line like `key = "value"`, then for the `nth` time
+/// the quoted value appears anywhere, for values in arrays. Returns the
+/// 1-based line and column of the value.
+pub(crate) fn locate(text: &str, key: &str, value: &str, nth: usize) -> Option<(usize, usize)> {
+    let quoted = [format!("\"{}\"", value), format!("'{}'", value)];
+    let mut found = 0;
+    for (i, line) in text.lines().enumerate() {
+        let rest = line.trim_start();
+        let rest = match rest.strip_prefix(key) {
+            Some(rest) => rest.trim_start(),
+            None => continue,
+        };
+        let rest = match rest.strip_prefix('=') {
+            Some(rest) => rest.trim_start(),
+            None => continue,
+        };
+        if quoted
+            .iter()
+            .any(|quoted| rest.starts_with(quoted.as_str()))
+        {
+            if found == nth {
+                return Some((i + 1, line.len() - rest.len() + 1));
+            }
+            found += 1;
+        }
+    }
+    let mut found = 0;
+    for (i, line) in text.lines().enumerate() {
+        for quoted in &quoted {
+            for (column, _) in line.match_indices(quoted.as_str()) {
+                if found == nth {
+                    return Some((i + 1, column + 1));
+                }
+                found += 1;
+            }
+        }
+    }
+    None
+}
+
+/// Checks a parsed config against its text and the folders it names.
+pub(crate) fn check_config(dirs: &Dirs, config: &Config, text: &str) -> Vec<BuildError> {
+    let mut problems = Vec::new();
+    let invalid = |key: &str, value: &str, nth: usize, message: String| {
+        let position = locate(text, key, value, nth);
+        BuildError::InvalidSetting {
+            path: dirs.config.clone(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message,
+        }
+    };
+
+    let (ignore, invalid_patterns) = ignore_patterns(dirs, config, text);
+    problems.extend(invalid_patterns);
+
+    if let Err(reason) = validate_base_path(&config.base_path) {
+        problems.push(invalid(
+            "base_path",
+            &config.base_path,
+            0,
+            format!("base_path {:?} {}", config.base_path, reason),
+        ));
+    }
+
+    problems.extend(check_slugs(dirs, config, text));
+    problems.extend(check_sizes(dirs, config, text));
+
+    let missing_folder = |path, what: &str| BuildError::MissingFolder {
+        path,
+        what: what.into(),
+    };
+    let missing_file = |path, what: &str| BuildError::MissingFile {
+        path,
+        what: what.into(),
+    };
+    let empty_folder = |path, what: &str| BuildError::EmptyFolder {
+        path,
+        what: what.into(),
+    };
+    for page in &config.pages {
+        let file = match &page.content_file {
+            Some(file) => file,
+            None => continue,
+        };
+        match join_inside(&dirs.input, file, "Content file") {
+            Ok(path) if !path.is_file() => problems.push(missing_file(path, "Content file")),
+            Ok(_) => {}
+            Err(err) => problems.push(err),
+        }
+        if !page.content.is_empty() {
+            problems.push(invalid(
+                "content_file",
+                &file.display().to_string(),
+                0,
+                format!(
+                    "The page {:?} has both content and a content_file, only the file is used",
+                    page.title
+                ),
+            ));
+        }
+    }
+    for comic in &config.comics {
+        if let Some(thumbnail) = &comic.thumbnail {
+            match join_inside(&dirs.input, thumbnail, "Thumbnail") {
+                Ok(path) if !path.is_file() => problems.push(missing_file(path, "Thumbnail")),
+                Ok(_) => {}
+                Err(err) => problems.push(err),
+            }
+        }
+        let comic_folder = match join_inside(&dirs.input, &comic.folder, "Comic folder") {
+            Ok(folder) => folder,
+            Err(err) => {
+                problems.push(err);
+                continue;
+            }
+        };
+        if !comic_folder.is_dir() {
+            problems.push(missing_folder(comic_folder, "Comic folder"));
+            continue;
+        }
+        for chapter in &comic.chapters {
+            match join_inside(&comic_folder, &chapter.folder, "Chapter folder") {
+                Ok(path) if !path.is_dir() => problems.push(missing_folder(path, "Chapter folder")),
+                Ok(_) => {}
+                Err(err) => problems.push(err),
+            }
+        }
+        for entry in &comic.order {
+            let what = "Page in the comic's order";
+            match join_inside(&comic_folder, entry, what) {
+                Ok(path) if !path.exists() => problems.push(missing_file(path, what)),
+                Ok(_) => {}
+                Err(err) => problems.push(err),
+            }
+        }
+        if let Some(page) = &comic.thumbnail_page {
+            match join_inside(&comic_folder, page, "Thumbnail page") {
+                Ok(path) if !path.is_file() => problems.push(missing_file(path, "Thumbnail page")),
+                Ok(_) => {}
+                Err(err) => problems.push(err),
+            }
+        }
+
+        // Files that aren't images are left out of the build with a note, and
+        // the build warns about pages in an unclear order. Neither is a
+        // problem with the config.
+        let mut skipped = Vec::new();
+        let (files, folders) = read_folder(&dirs.input, &comic_folder, &ignore, &mut skipped);
+        if files.is_empty() && folders.is_empty() {
+            problems.push(empty_folder(comic_folder, "Comic folder"));
+            continue;
+        }
+        let url = comic.folder.display().to_string();
+        let folders = order_paths(folders, comic, &comic_folder, &mut skipped);
+        problems.extend(check_chapters(&url, &folders));
+        for folder in folders {
+            let (files, _) = read_folder(&dirs.input, &folder, &ignore, &mut skipped);
+            if files.is_empty() {
+                problems.push(empty_folder(folder, "Chapter folder"));
+            }
+        }
+    }
+    problems
+}
+
+/// Finds image and thumbnail sizes of 0, which can't be made.
+pub(crate) fn check_sizes(dirs: &Dirs, config: &Config, text: &str) -> Vec<BuildError> {
+    let mut problems = Vec::new();
+    let mut too_small = |key: &str, message: &str| {
+        let position = locate_key(text, key);
+        problems.push(BuildError::InvalidSetting {
+            path: dirs.config.clone(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message: message.into(),
+        });
+    };
+    if config.images.widths.contains(&0) {
+        too_small("widths", "Image widths have to be at least 1 pixel");
+    }
+    if config.thumbnails.width == 0 {
+        too_small("width", "The thumbnail width has to be at least 1 pixel");
+    }
+    if config.thumbnails.height == 0 {
+        too_small("height", "The thumbnail height has to be at least 1 pixel");
+    }
+    problems
+}
+
+/// A page's or comic's URL, as it's written in the config.
+struct Slug {
+    key: &'static str,
+    value: String,
+    /// Like `page "about"`.
+    what: String,
+    is_comic: bool,
+    /// Which of the values for `key` in the config this is, to locate it.
+    nth: usize,
+}
+
+/// Why a page or comic slug would make a path outside the output folder.
+pub(crate) fn unsafe_slug(slug: &str) -> Option<&'static str> {
+    if slug.trim_matches('/').is_empty() {
+        Some("is empty, so it would replace the front page")
+    } else if slug.starts_with('/') || slug.starts_with('\\') || Path::new(slug).is_absolute() {
+        Some("is an absolute path, but it has to be relative to the site")
+    } else if slug.contains(':') {
+        Some("can't have a : in it")
+    } else if slug
+        .split(['/', '\\'])
+        .any(|segment| segment == "." || segment == "..")
+    {
+        Some("has . or .. in it, which would put it in the wrong place")
+    } else {
+        None
+    }
+}
+
+/// Finds pages and comics that would be written to the same place, or
+/// outside the output folder, or over the site's own files. URLs that only
+/// differ in case clash too, as they're the same folder on Windows and macOS.
+pub(crate) fn check_slugs(dirs: &Dirs, config: &Config, text: &str) -> Vec<BuildError> {
+    let mut reserved: Vec<String> = RESERVED.iter().map(|name| name.to_string()).collect();
+    if let Ok(entries) = fs::read_dir(&dirs.static_files) {
+        for entry in entries.flatten() {
+            reserved.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+
+    let mut counts = HashMap::new();
+    let mut slug = |key: &'static str, value: String, what: String, is_comic: bool| {
+        let nth = counts.entry((key, value.clone())).or_insert(0);
+        *nth += 1;
+        Slug {
+            key,
+            value,
+            what,
+            is_comic,
+            nth: *nth - 1,
+        }
+    };
+    let mut slugs = Vec::new();
+    for page in &config.pages {
+        let what = format!("page {:?}", page.title);
+        slugs.push(slug("page", page.page.clone(), what, false));
+    }
+    for comic in &config.comics {
+        let what = format!("comic {:?}", comic.title);
+        slugs.push(slug(
+            "folder",
+            comic.folder.display().to_string(),
+            what,
+            true,
+        ));
+    }
+
+    let mut problems = Vec::new();
+    let mut bad = |slug: &Slug, message: String| {
+        let position = locate(text, slug.key, &slug.value, slug.nth);
+        problems.push(BuildError::BadSlug {
+            path: dirs.config.clone(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message,
+        });
+    };
+    let mut checked: Vec<(&Slug, String)> = Vec::new();
+    for slug in &slugs {
+        if let Some(reason) = unsafe_slug(&slug.value) {
+            bad(
+                slug,
+                format!("The URL {:?} of the {} {}", slug.value, slug.what, reason),
+            );
+            continue;
+        }
+        let url = slug.value.trim_matches('/').to_lowercase();
+        let first = url.split('/').next().unwrap_or_default();
+        if let Some(name) = reserved.iter().find(|name| name.to_lowercase() == first) {
+            let message = format!(
+                "The URL {:?} of the {} clashes with the site's own {:?}",
+                slug.value, slug.what, name
+            );
+            bad(slug, message);
+        }
+        for (other, other_url) in &checked {
+            if slug.value.trim_matches('/') == other.value.trim_matches('/') {
+                let message = format!(
+                    "The {} has the same URL as the {}: {:?}",
+                    slug.what, other.what, slug.value
+                );
+                bad(slug, message);
+            } else if url == *other_url {
+                let message = format!(
+                    "The URLs of the {} and the {}, {:?} and {:?}, only differ in case, \
+                     so they're the same on Windows and macOS",
+                    slug.what, other.what, slug.value, other.value
+                );
+                bad(slug, message);
+            } else {
+                let pairs = [
+                    (slug, &url, *other, other_url),
+                    (*other, other_url, slug, &url),
+                ];
+                for &(inner, inner_url, comic, comic_url) in &pairs {
+                    if comic.is_comic && inner_url.starts_with(&format!("{}/", comic_url)) {
+                        let message = format!(
+                            "The URL {:?} of the {} is inside the URL of the {}, where its pages go",
+                            inner.value, inner.what, comic.what
+                        );
+                        bad(inner, message);
+                    }
+                }
+            }
+        }
+        checked.push((slug, url));
+    }
+    problems
+}
+
+/// Finds chapters of the comic at `url` whose names only differ in case, so
+/// they'd be written to the same folder on Windows and macOS.
+pub(crate) fn check_chapters(url: &str, chapters: &[PathBuf]) -> Vec<BuildError> {
+    let mut problems = Vec::new();
+    let mut checked: Vec<(String, String)> = Vec::new();
+    for folder in chapters {
+        let name = folder.file_name().unwrap_or_default().to_string_lossy();
+        let chapter = chapter_url(url, &name);
+        let lowercase = chapter.to_lowercase();
+        if let Some((other, _)) = checked.iter().find(|(_, other)| *other == lowercase) {
+            problems.push(BuildError::BadSlug {
+                path: folder.clone(),
+                line: None,
+                column: None,
+                message: format!(
+                    "The URLs of the chapters {:?} and {:?}, {:?} and {:?}, only differ in \
+                     case, so they're the same on Windows and macOS",
+                    name,
+                    Path::new(other).file_name().unwrap_or_default(),
+                    chapter,
+                    other
+                ),
+            });
+        }
+        checked.push((chapter, lowercase));
+    }
+    problems
+}
+
+/// Whether `base_path` can be the path part of a URL. Returns why it can't.
+pub(crate) fn validate_base_path(base_path: &str) -> Result<(), String> {
+    if base_path.contains("://") {
+        return Err("should be only the path part of the site's URL, without the domain".into());
+    }
+    if let Some(c) = base_path
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || "?#\\\"<>".contains(*c))
+    {
+        return Err(format!("can't have {:?} in it", c));
+    }
+    if base_path
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+    {
+        return Err("can't have . or .. in it".into());
+    }
+    Ok(())
+}
+
+/// Reads the config file and checks it. Fails only if it can't be read or
+/// parsed.
+pub(crate) fn check(dirs: &Dirs) -> Result<Vec<BuildError>, BuildError> {
+    let text = fs::read_to_string(&dirs.config).map_err(|err| BuildError::io(&dirs.config, err))?;
+    let config = Config::parse(&dirs.config, &text)?;
+    Ok(check_config(dirs, &config, &text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_path_must_be_a_url_path() {
+        for base_path in &["", "/", "comic", "/my/comic/", "comic-2019"] {
+            assert_eq!(validate_base_path(base_path), Ok(()));
+        }
+        for base_path in &[
+            "https://example.com/comic",
+            "my comic",
+            "comic?x",
+            "../comic",
+        ] {
+            assert!(validate_base_path(base_path).is_err());
+        }
+    }
+
+    #[test]
+    fn comics_cant_be_inside_comics() {
+        let text = r#"
+            title = "Test"
+
+            [[pages]]
+            page = "comic/about"
+            title = "About"
+
+            [[comics]]
+            folder = "comic"
+            title = "Comic"
+            description = ""
+
+            [[comics]]
+            folder = "comic/latest"
+            title = "Extras"
+            description = ""
+
+            [[comics]]
+            folder = "comics"
+            title = "Others"
+            description = ""
+        "#;
+        let dirs = Dirs::default();
+        let config = Config::parse(&dirs.config, text).unwrap();
+        let lines: Vec<_> = check_slugs(&dirs, &config, text)
+            .iter()
+            .map(|problem| problem.position().map(|(line, _)| line))
+            .collect();
+        assert_eq!(lines, [Some(5), Some(14)]);
+    }
+
+    #[test]
+    fn chapter_names_cant_only_differ_in_case() {
+        let chapters = [
+            PathBuf::from("input/comic/part1"),
+            PathBuf::from("input/comic/part2"),
+        ];
+        assert!(check_chapters("comic", &chapters).is_empty());
+        let chapters = [
+            PathBuf::from("input/comic/Part1"),
+            PathBuf::from("input/comic/part1"),
+        ];
+        let problems = check_chapters("comic", &chapters);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path(), chapters[1]);
+    }
+
+    #[test]
+    fn sizes_cant_be_0() {
+        let text = "title = \"Test\"\npages = []\ncomics = []\n\n[images]\nwidths = [0, 480]\n\n\
+                    [thumbnails]\nwidth = 10\nheight = 0\n";
+        let dirs = Dirs::default();
+        let config = Config::parse(&dirs.config, text).unwrap();
+        let lines: Vec<_> = check_sizes(&dirs, &config, text)
+            .iter()
+            .map(|problem| problem.position().map(|(line, _)| line))
+            .collect();
+        assert_eq!(lines, [Some(6), Some(10)]);
+    }
+
+    #[test]
+    fn settings_are_located() {
+        let text = "[[pages]]\npage = \"about\"\n\n[[pages]]\n  page='about'\n\
+                    ignore = [\"a\", \"[b\"]\n";
+        assert_eq!(locate(text, "page", "about", 0), Some((2, 8)));
+        assert_eq!(locate(text, "page", "about", 1), Some((5, 8)));
+        assert_eq!(locate(text, "ignore", "[b", 0), Some((6, 16)));
+        assert_eq!(locate(text, "page", "contact", 0), None);
+    }
+
+    #[test]
+    fn slugs_stay_inside_the_site() {
+        for slug in &["about", "comic/extras", "about/", "..hidden"] {
+            assert_eq!(unsafe_slug(slug), None);
+        }
+        for slug in &[
+            "",
+            "/",
+            "/etc",
+            "../about",
+            "comic/../../x",
+            "a\\..\\b",
+            "C:/x",
+        ] {
+            assert!(unsafe_slug(slug).is_some(), "{:?} should be unsafe", slug);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! The command line front-end, shared by `comic-publisher` and the programs
+//! that each do one of its commands.
+
+use crate::{
+    serve::ServeOptions, BuildError, BuildOptions, BuildReport, DirOptions, Dirs, Severity,
+};
+use std::{error::Error, io, net::IpAddr, path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+
+/// Builds comic websites, previews them, and publishes them to GitHub Pages.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "comic-publisher")]
+pub struct Cli {
+    #[structopt(flatten)]
+    pub global: Global,
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+// Flags every command takes. Not a doc comment, as structopt would use it as
+// the about text of every program that flattens it in.
+#[derive(StructOpt, Debug)]
+pub struct Global {
+    /// The folder with the comics in it, and the config file unless --config
+    /// says otherwise. Defaults to input.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub input: Option<PathBuf>,
+    /// The folder the site is built into. Defaults to output.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+    /// The config file. Defaults to config.toml in the input folder. When
+    /// it's given, its folder is the input folder and the other folders are
+    /// next to that, unless they're given too.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+    /// The folder with the page templates. Defaults to templates.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub templates: Option<PathBuf>,
+    /// The folder with files to copy into the site as they are. Defaults to
+    /// static.
+    #[structopt(long = "static", global = true, parse(from_os_str))]
+    pub static_files: Option<PathBuf>,
+    /// Where to write the errors of a build. Defaults to errors.txt.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub errors: Option<PathBuf>,
+    /// Only print errors.
+    #[structopt(short, long, global = true)]
+    pub quiet: bool,
+}
+
+impl Global {
+    /// The folders given here, with the rest coming from the config's
+    /// `[paths]` or the defaults.
+    pub fn dirs(&self) -> Result<Dirs, BuildError> {
+        Dirs::load(DirOptions {
+            input: self.input.clone(),
+            output: self.output.clone(),
+            config: self.config.clone(),
+            templates: self.templates.clone(),
+            static_files: self.static_files.clone(),
+            errors: self.errors.clone(),
+        })
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Creates the input folder, with an example config and comic folder.
+    Init,
+    /// Builds the site into the output folder.
+    Build(BuildArgs),
+    /// Previews the site in your browser, rebuilding it whenever it changes.
+    Serve(ServeArgs),
+    /// Uploads the built site to GitHub Pages.
+    Publish,
+    /// Checks the config for problems without building anything.
+    Check,
+    /// Removes everything the build put in the output folder.
+    Clean,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct BuildArgs {
+    /// Leave files in the output folder that aren't part of the site anymore,
+    /// instead of removing them.
+    #[structopt(long)]
+    pub keep_stale: bool,
+    /// How many images and pages to work on at once. Defaults to the number
+    /// of CPUs.
+    #[structopt(short, long)]
+    pub jobs: Option<usize>,
+    /// Keep running, and rebuild whenever the input, templates or static
+    /// files change.
+    #[structopt(short, long)]
+    pub watch: bool,
+    /// How to print what the build did and the problems it found: text, json
+    /// or html.
+    #[structopt(long, default_value = "text")]
+    pub report: ReportFormat,
+}
+
+/// The ways `build --report` can print a build report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ReportFormat, String> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!(
+                "{:?} isn't a report format, use text, json or html",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ServeArgs {
+    /// The address to listen on. Use 0.0.0.0 to let other devices on your
+    /// network, like your phone, see the site. Defaults to 127.0.0.1.
+    #[structopt(long)]
+    pub host: Option<IpAddr>,
+    /// The port to listen on. Defaults to 8888, or the next free port after
+    /// it if that's taken.
+    #[structopt(short, long)]
+    pub port: Option<u16>,
+    /// Send the same headers as the static host the site is published to,
+    /// and serve pages exactly as they're built, without reloading them
+    /// when the site changes.
+    #[structopt(long)]
+    pub production_headers: bool,
+}
+
+pub fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Init => init(&cli.global),
+        Command::Build(args) => build(&cli.global, &args),
+        Command::Serve(args) => serve(&cli.global, &args),
+        Command::Publish => publish(&cli.global),
+        Command::Check => check(&cli.global),
+        Command::Clean => clean(&cli.global),
+    }
+}
+
+/// For the programs that get double-clicked on Windows, where the window
+/// closes as soon as the program ends: shows the error until enter is pressed.
+pub fn pause_on_error(result: Result<(), Box<dyn Error>>) {
+    if let Err(err) = result {
+        println!("Error: {}", err);
+        println!("Press [enter] to finish.");
+        io::stdin().read_line(&mut String::new()).unwrap();
+    }
+}
+
+pub fn init(global: &Global) -> Result<(), Box<dyn Error>> {
+    let dirs = global.dirs()?;
+    let created = crate::init(&dirs)?;
+    if !global.quiet {
+        if created {
+            println!(
+                "Made {}. Read README.txt in it to see how to add your comics.",
+                dirs.input.display()
+            );
+        } else {
+            println!("{} is already set up.", dirs.input.display());
+        }
+    }
+    Ok(())
+}
+
+pub fn build(global: &Global, args: &BuildArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+    let dirs = match global.dirs() {
+        Ok(dirs) => dirs,
+        Err(err) => {
+            print_report(&BuildReport::from(err), args.report, global.quiet);
+            return Err("The config couldn't be read, or names folders that can't be used.".into());
+        }
+    };
+    let options = BuildOptions {
+        keep_stale: args.keep_stale,
+    };
+
+    if crate::init(&dirs)? {
+        return Ok(());
+    }
+
+    if args.watch {
+        return Ok(watch(&dirs, &options, global.quiet)?);
+    }
+
+    // Problems that stop the build are reported the same way as the rest.
+    let report = crate::build(&dirs, &options).unwrap_or_else(BuildReport::from);
+    crate::write_errors(&dirs, &report)?;
+    print_report(&report, args.report, global.quiet);
+    if report.has_errors() {
+        Err("Some errors occurred.".into())
+    } else {
+        Ok(())
+    }
+}
+
+fn print_report(report: &BuildReport, format: ReportFormat, quiet: bool) {
+    match format {
+        ReportFormat::Text => report.print(quiet),
+        ReportFormat::Json => println!("{}", report.json()),
+        ReportFormat::Html => print!("{}", report.html()),
+    }
+}
+
+/// Builds the site, then rebuilds it whenever something it's built from
+/// changes, until the program is stopped.
+fn watch(dirs: &Dirs, options: &BuildOptions, quiet: bool) -> io::Result<()> {
+    rebuild(dirs, options, quiet);
+    crate::watch(dirs, |changed| {
+        if !quiet {
+            println!();
+            println!("Rebuilding after changes to:");
+            for path in changed {
+                println!("  {}", path);
+            }
+        }
+        rebuild(dirs, options, quiet);
+    })
+}
+
+/// Builds the site, printing errors rather than stopping on them.
+fn rebuild(dirs: &Dirs, options: &BuildOptions, quiet: bool) {
+    let report = crate::build(dirs, options).unwrap_or_else(BuildReport::from);
+    if let Err(err) = crate::write_errors(dirs, &report) {
+        println!("Error: {}", err);
+    }
+    report.print(quiet);
+    if !quiet {
+        let watched: Vec<_> = dirs
+            .watched()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        println!(
+            "Watching {} for changes. Press Ctrl+C to stop.",
+            watched.join(", ")
+        );
+    }
+}
+
+pub fn serve(global: &Global, args: &ServeArgs) -> Result<(), Box<dyn Error>> {
+    crate::serve::serve(ServeOptions {
+        dirs: global.dirs()?,
+        host: args.host,
+        port: args.port,
+        production_headers: args.production_headers,
+        quiet: global.quiet,
+    })
+}
+
+pub fn publish(global: &Global) -> Result<(), Box<dyn Error>> {
+    crate::publish::publish(&global.dirs()?, global.quiet)
+}
+
+pub fn check(global: &Global) -> Result<(), Box<dyn Error>> {
+    let result = global.dirs().and_then(|dirs| {
+        let problems = crate::check(&dirs)?;
+        Ok((dirs, problems))
+    });
+    let (dirs, problems) = match result {
+        Ok(checked) => checked,
+        Err(err) => {
+            println!("{}: {}: {}", err.location(), err.severity(), err);
+            return Err("The config couldn't be read, or names folders that can't be used.".into());
+        }
+    };
+    for problem in &problems {
+        println!(
+            "{}: {}: {}",
+            problem.location(),
+            problem.severity(),
+            problem
+        );
+    }
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity() == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(format!("Found {} errors in {}.", errors, dirs.config.display()).into());
+    }
+    if !global.quiet {
+        println!("No problems found in {}.", dirs.config.display());
+    }
+    Ok(())
+}
+
+pub fn clean(global: &Global) -> Result<(), Box<dyn Error>> {
+    let dirs = global.dirs()?;
+    let removed = crate::clean(&dirs)?;
+    if !global.quiet {
+        println!("Removed {} files from {}.", removed, dirs.output.display());
+    }
+    Ok(())
+}
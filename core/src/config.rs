@@ -0,0 +1,242 @@
+//! The config file, `config.toml` in the input folder by default.
+
+use crate::error::BuildError;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub title: String,
+    pub author: Option<String>,
+    pub pages: Vec<ImportPage>,
+    pub comics: Vec<ImportComic>,
+    pub copyright: Option<String>,
+    #[serde(default = "default_base_path")]
+    pub base_path: String,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    #[serde(default)]
+    pub thumbnails: ThumbnailConfig,
+    #[serde(default)]
+    pub images: ImageConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    pub github: Option<GitHubConfig>,
+    #[serde(default)]
+    pub paths: PathConfig,
+}
+
+impl Config {
+    /// Reads and parses a config file.
+    pub fn load(path: &Path) -> Result<Config, BuildError> {
+        let text = fs::read_to_string(path).map_err(|err| BuildError::io(path, err))?;
+        Config::parse(path, &text)
+    }
+
+    /// Parses the text of the config file at `path`. Settings it doesn't
+    /// know are errors, so typos don't go unnoticed.
+    pub fn parse(path: &Path, text: &str) -> Result<Config, BuildError> {
+        toml::from_str(text).map_err(|err| BuildError::toml_in(path, text, &err))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThumbnailConfig {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        ThumbnailConfig {
+            width: 480,
+            height: 400,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageConfig {
+    pub widths: Vec<u32>,
+    pub webp: bool,
+    pub sizes: String,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            widths: vec![480, 960, 1600],
+            webp: false,
+            sizes: "(max-width: 800px) 100vw, 800px".into(),
+        }
+    }
+}
+
+/// Files that are never comic pages, ignored on top of the config's `ignore`.
+pub(crate) static DEFAULT_IGNORE: &[&str] = &[".*", "Thumbs.db", "desktop.ini"];
+
+fn default_base_path() -> String {
+    "/".into()
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ImportComic {
+    pub folder: PathBuf,
+    pub thumbnail: Option<PathBuf>,
+    pub thumbnail_page: Option<PathBuf>,
+    pub title: String,
+    pub description: String,
+    /// What the description, and the chapters' descriptions, are written in.
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub chapters: Vec<ImportChapter>,
+    #[serde(default)]
+    pub sort: Sort,
+    #[serde(default)]
+    pub order: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Natural,
+    Name,
+    Mtime,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ImportChapter {
+    pub folder: PathBuf,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Paged,
+    Scroll,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ImportPage {
+    pub page: String,
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    /// A file in the input folder to read the content from, instead of
+    /// `content`.
+    pub content_file: Option<PathBuf>,
+    /// What the content is written in. Defaults to Markdown for a
+    /// `content_file` ending in `.md`, and HTML otherwise.
+    pub format: Option<Format>,
+}
+
+impl ImportPage {
+    /// What the content is written in, going by the content file's extension
+    /// if `format` isn't given.
+    pub fn format(&self) -> Format {
+        let extension = self.content_file.as_ref().and_then(|file| file.extension());
+        match (self.format, extension) {
+            (Some(format), _) => format,
+            (None, Some(extension)) if extension == "md" || extension == "markdown" => {
+                Format::Markdown
+            }
+            (None, _) => Format::Html,
+        }
+    }
+}
+
+/// What a page's content or a comic's description is written in.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl Format {
+    /// Turns text in this format into HTML.
+    pub fn to_html(self, text: &str) -> String {
+        match self {
+            Format::Html => text.to_string(),
+            Format::Markdown => crate::markdown::to_html(text),
+        }
+    }
+}
+
+/// Where the preview server makes the site available.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: Option<IpAddr>,
+    pub port: Option<u16>,
+}
+
+/// The repository the site is published to.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GitHubConfig {
+    pub username: String,
+    pub repository: String,
+    pub domain: Option<String>,
+    pub author: Option<String>,
+    pub email: Option<String>,
+}
+
+/// The `[paths]` section of the config. Relative paths are relative to the
+/// folder the config file is in.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathConfig {
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub templates: Option<PathBuf>,
+    #[serde(rename = "static")]
+    pub static_files: Option<PathBuf>,
+    pub errors: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_files_ending_in_md_are_markdown() {
+        assert_eq!(Format::Html.to_html("<b>*x*</b>"), "<b>*x*</b>");
+        let page = |content_file: Option<&str>, format| ImportPage {
+            page: "about".into(),
+            title: "About".into(),
+            content: String::new(),
+            content_file: content_file.map(PathBuf::from),
+            format,
+        };
+        assert_eq!(page(None, None).format(), Format::Html);
+        assert_eq!(page(Some("about.md"), None).format(), Format::Markdown);
+        assert_eq!(page(Some("about.html"), None).format(), Format::Html);
+        assert_eq!(
+            page(Some("about.md"), Some(Format::Html)).format(),
+            Format::Html
+        );
+        assert_eq!(
+            page(None, Some(Format::Markdown)).format(),
+            Format::Markdown
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Where a site's folders are.
+
+use crate::{config::PathConfig, error::BuildError};
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Where a site's files are read from and written to.
+#[derive(Debug, Clone)]
+pub struct Dirs {
+    /// The comics, and the config file unless it's somewhere else.
+    pub input: PathBuf,
+    /// Where the site is built.
+    pub output: PathBuf,
+    /// The config file.
+    pub config: PathBuf,
+    /// The Tera templates the pages are rendered with.
+    pub templates: PathBuf,
+    /// Files copied into the site as they are.
+    pub static_files: PathBuf,
+    /// Where the errors of the last build are written.
+    pub errors: PathBuf,
+}
+
+/// Folders given on the command line, which win over the config's `[paths]`.
+/// Anything left out of both is the usual folder in the current directory.
+#[derive(Debug, Default, Clone)]
+pub struct DirOptions {
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub templates: Option<PathBuf>,
+    pub static_files: Option<PathBuf>,
+    pub errors: Option<PathBuf>,
+}
+
+impl Default for Dirs {
+    fn default() -> Dirs {
+        Dirs {
+            input: PathBuf::from("input"),
+            output: PathBuf::from("output"),
+            config: PathBuf::from("input/config.toml"),
+            templates: PathBuf::from("templates"),
+            static_files: PathBuf::from("static"),
+            errors: PathBuf::from("errors.txt"),
+        }
+    }
+}
+
+impl Dirs {
+    /// Works out the folders from the command line and the config file's
+    /// `[paths]`. A missing config file is fine, as `init` may be about to
+    /// make it, but one that doesn't parse isn't.
+    ///
+    /// When the config file is given, folders given nowhere are found around
+    /// it the way they are by default: the config file's folder is the input
+    /// folder, and the others are next to it.
+    pub fn load(options: DirOptions) -> Result<Dirs, BuildError> {
+        let defaults = match &options.config {
+            Some(config) => Dirs::around(config),
+            None => Dirs::default(),
+        };
+        let config = match (&options.config, &options.input) {
+            (Some(config), _) => config.clone(),
+            (None, Some(input)) => input.join("config.toml"),
+            (None, None) => defaults.config.clone(),
+        };
+
+        #[derive(Deserialize, Default)]
+        struct Paths {
+            #[serde(default)]
+            paths: PathConfig,
+        }
+        let paths = match fs::read_to_string(&config) {
+            Ok(text) => {
+                toml::from_str::<Paths>(&text).map_err(|err| BuildError::toml(&config, &err))?
+            }
+            Err(_) => Paths::default(),
+        }
+        .paths;
+        let base = config.parent().unwrap_or_else(|| Path::new(""));
+        let pick = |given: Option<PathBuf>, configured: Option<PathBuf>, default: PathBuf| {
+            given
+                .or_else(|| configured.map(|path| base.join(path)))
+                .unwrap_or(default)
+        };
+
+        let dirs = Dirs {
+            input: pick(options.input, paths.input, defaults.input),
+            output: pick(options.output, paths.output, defaults.output),
+            templates: pick(options.templates, paths.templates, defaults.templates),
+            static_files: pick(
+                options.static_files,
+                paths.static_files,
+                defaults.static_files,
+            ),
+            errors: pick(options.errors, paths.errors, defaults.errors),
+            config,
+        };
+        dirs.check_output()?;
+        Ok(dirs)
+    }
+
+    /// Makes sure a build can't touch what the site is built from: the output
+    /// folder can't be or hold the input, templates or static folders or the
+    /// config file, and can't be inside a folder that's watched for changes.
+    fn check_output(&self) -> Result<(), BuildError> {
+        let output = resolve(&self.output);
+        let sources = [
+            (&self.input, "the input folder"),
+            (&self.templates, "the templates folder"),
+            (&self.static_files, "the static folder"),
+            (&self.config, "the config file"),
+        ];
+        for (path, what) in sources.iter() {
+            let source = resolve(path);
+            let reason = if source == output {
+                format!("is {} {:?}", what, path)
+            } else if source.starts_with(&output) {
+                format!("holds {} {:?}", what, path)
+            } else if output.starts_with(&source) {
+                format!(
+                    "is inside {} {:?}, which is watched for changes",
+                    what, path
+                )
+            } else {
+                continue;
+            };
+            return Err(BuildError::OutputFolder {
+                path: self.output.clone(),
+                reason,
+            });
+        }
+        Ok(())
+    }
+
+    /// The usual folders for a config file at `config`.
+    fn around(config: &Path) -> Dirs {
+        let input = match config.parent() {
+            Some(folder) if !folder.as_os_str().is_empty() => folder.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let beside = |name: &str| match input.parent() {
+            Some(parent) if input.file_name().is_some() => parent.join(name),
+            _ => input.join("..").join(name),
+        };
+        Dirs {
+            output: beside("output"),
+            config: config.to_path_buf(),
+            templates: beside("templates"),
+            static_files: beside("static"),
+            errors: beside("errors.txt"),
+            input,
+        }
+    }
+
+    /// The files and folders a site is built from.
+    pub fn watched(&self) -> Vec<PathBuf> {
+        let mut watched = vec![
+            self.input.clone(),
+            self.templates.clone(),
+            self.static_files.clone(),
+        ];
+        if !self.config.starts_with(&self.input) {
+            watched.push(self.config.clone());
+        }
+        watched
+    }
+}
+
+/// Joins a path from the config onto the folder it's relative to, making
+/// sure it stays inside: it can't be absolute, climb out with `..`, or lead
+/// out through a link. `what` says what the path is for, like "Thumbnail".
+pub(crate) fn join_inside(root: &Path, path: &Path, what: &str) -> Result<PathBuf, BuildError> {
+    let outside = |reason: String| BuildError::OutsideFolder {
+        path: root.join(path),
+        what: what.into(),
+        reason,
+    };
+    let mut inside = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => inside.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !inside.pop() {
+                    return Err(outside(format!("goes outside {:?} with ..", root)));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(outside(format!(
+                    "is an absolute path, not one inside {:?}",
+                    root
+                )));
+            }
+        }
+    }
+    let joined = root.join(inside);
+    // Paths that don't exist yet can't lead anywhere, and are reported as
+    // missing elsewhere.
+    if let (Ok(canonical_root), Ok(canonical)) = (root.canonicalize(), joined.canonicalize()) {
+        if !canonical.starts_with(&canonical_root) {
+            return Err(outside(format!("leads outside {:?} through a link", root)));
+        }
+    }
+    Ok(joined)
+}
+
+/// Where `path` really is, with `.`, `..` and links worked out, even if it
+/// doesn't exist yet, so folders can be compared however they were written.
+pub(crate) fn resolve(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    let mut normal = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    // Links can only be followed in the part that exists.
+    let mut existing = normal.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(mut resolved) = existing.canonicalize() {
+            resolved.extend(rest.iter().rev());
+            return resolved;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return normal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_given_config_brings_its_folders() {
+        let dirs = Dirs::around(Path::new("/sites/one/input/config.toml"));
+        assert_eq!(dirs.input, Path::new("/sites/one/input"));
+        assert_eq!(dirs.output, Path::new("/sites/one/output"));
+        assert_eq!(dirs.templates, Path::new("/sites/one/templates"));
+        assert_eq!(dirs.static_files, Path::new("/sites/one/static"));
+        assert_eq!(dirs.errors, Path::new("/sites/one/errors.txt"));
+
+        let defaults = Dirs::default();
+        let dirs = Dirs::around(&defaults.config);
+        assert_eq!(dirs.input, defaults.input);
+        assert_eq!(dirs.output, defaults.output);
+        assert_eq!(dirs.templates, defaults.templates);
+
+        let dirs = Dirs::around(Path::new("config.toml"));
+        assert_eq!(dirs.input, Path::new("."));
+        assert_eq!(dirs.output, Path::new("./../output"));
+    }
+
+    #[test]
+    fn output_cant_overlap_what_the_site_is_built_from() {
+        let load = |output: &str| {
+            Dirs::load(DirOptions {
+                config: Some(PathBuf::from("site/input/config.toml")),
+                output: Some(PathBuf::from(output)),
+                ..DirOptions::default()
+            })
+        };
+        assert!(load("site/output").is_ok());
+        assert!(load("site/input/../output").is_ok());
+        assert!(load("site").is_err());
+        assert!(load("site/input").is_err());
+        assert!(load("site/input/../input/").is_err());
+        assert!(load("site/input/output").is_err());
+        assert!(load("site/templates").is_err());
+        assert!(load("site/static/copy").is_err());
+        assert!(load(".").is_err());
+    }
+
+    #[test]
+    fn config_paths_stay_inside_their_folder() {
+        let root = Path::new("input");
+        let join = |path: &str| join_inside(root, Path::new(path), "Comic folder").ok();
+        assert_eq!(join("comic"), Some(root.join("comic")));
+        assert_eq!(join("./comic/ch1"), Some(root.join("comic/ch1")));
+        assert_eq!(join("comic/../other"), Some(root.join("other")));
+        assert_eq!(join("comic/.."), Some(root.to_path_buf()));
+        assert_eq!(join(".."), None);
+        assert_eq!(join("../input/comic"), None);
+        assert_eq!(join("comic/../../secret"), None);
+        assert_eq!(join("/etc/passwd"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn config_paths_cant_leave_through_links() {
+        use crate::test_folder;
+        let folder = test_folder("links");
+        let root = folder.join("input");
+        fs::create_dir_all(root.join("comic")).unwrap();
+        fs::create_dir_all(folder.join("secret")).unwrap();
+        std::os::unix::fs::symlink(folder.join("secret"), root.join("link")).unwrap();
+        assert!(join_inside(&root, Path::new("comic"), "Comic folder").is_ok());
+        assert!(join_inside(&root, Path::new("link"), "Comic folder").is_err());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,392 @@
+//! The problems a build can run into.
+
+use serde::{Serialize, Serializer};
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// How bad a problem is.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, but nothing is wrong with the site.
+    Info,
+    /// The site was built, but maybe not the way it was meant to be.
+    Warning,
+    /// Something was left out of the site.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "Note",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        })
+    }
+}
+
+/// Something that went wrong while loading or building a site, with the file
+/// it's about.
+#[derive(Debug, Clone)]
+pub enum BuildError {
+    /// The config, or a `chapter.toml`, couldn't be parsed. The line and
+    /// column are 1-based.
+    Config {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// A setting that parsed, but can't be used, like two pages with the same
+    /// slug.
+    InvalidSetting {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// A page or comic whose URL is the same as another's, or is outside the
+    /// site. The build stops before writing anything, as one would be
+    /// written over the other.
+    BadSlug {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// An output folder that's the same as, holds, or is inside a folder or
+    /// file the site is built from, so building could overwrite or remove it.
+    OutputFolder { path: PathBuf, reason: String },
+    /// A folder the config names doesn't exist. `what` says what the folder
+    /// is for, like "Comic folder".
+    MissingFolder { path: PathBuf, what: String },
+    /// A file the config names doesn't exist.
+    MissingFile { path: PathBuf, what: String },
+    /// A path in the config that leads outside the folder it's relative to.
+    /// It's left out rather than read.
+    OutsideFolder {
+        path: PathBuf,
+        what: String,
+        reason: String,
+    },
+    /// A comic or chapter folder with no pages in it.
+    EmptyFolder { path: PathBuf, what: String },
+    /// A folder inside a chapter, which isn't supported.
+    NestedFolder { path: PathBuf },
+    /// A file in a comic folder that was left out for not being an image.
+    NotAnImage { path: PathBuf, reason: String },
+    /// Pages whose order was decided by accident.
+    AmbiguousOrder { path: PathBuf, message: String },
+    /// A page image couldn't be read or decoded.
+    UnreadableImage { path: PathBuf, message: String },
+    /// A thumbnail couldn't be made.
+    Thumbnail {
+        comic: String,
+        path: PathBuf,
+        message: String,
+    },
+    /// The templates couldn't be parsed, or one of them couldn't be rendered.
+    Template {
+        path: PathBuf,
+        page: Option<String>,
+        message: String,
+    },
+    /// A file couldn't be copied or written to the output folder.
+    Copy {
+        from: Option<PathBuf>,
+        path: PathBuf,
+        message: String,
+    },
+    /// Any other file that couldn't be read or written.
+    Io { path: PathBuf, message: String },
+}
+
+impl BuildError {
+    /// A `Config` error for a TOML file that didn't parse.
+    pub(crate) fn toml(path: &Path, err: &toml::de::Error) -> BuildError {
+        let (line, column) = match err.line_col() {
+            Some((line, column)) => (Some(line + 1), Some(column + 1)),
+            None => (None, None),
+        };
+        BuildError::Config {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: err.to_string(),
+        }
+    }
+
+    /// Like `toml`, but when the error doesn't say where it is and it's about
+    /// an unknown setting, finds the setting in `text`.
+    pub(crate) fn toml_in(path: &Path, text: &str, err: &toml::de::Error) -> BuildError {
+        let mut error = BuildError::toml(path, err);
+        if let BuildError::Config {
+            line,
+            column,
+            message,
+            ..
+        } = &mut error
+        {
+            let unknown = message
+                .strip_prefix("unknown field `")
+                .and_then(|rest| rest.split('`').next());
+            if let (None, Some(key)) = (*line, unknown) {
+                if let Some((l, c)) = locate_key(text, key) {
+                    *line = Some(l);
+                    *column = Some(c);
+                }
+            }
+        }
+        error
+    }
+
+    pub(crate) fn io(path: &Path, err: impl fmt::Display) -> BuildError {
+        BuildError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        }
+    }
+
+    /// A `Template` error, with the reasons Tera gives for it.
+    pub(crate) fn template(path: &Path, page: Option<String>, err: &dyn Error) -> BuildError {
+        let mut message = err.to_string();
+        let mut source = err.source();
+        while let Some(err) = source {
+            message.push_str(": ");
+            message.push_str(&err.to_string());
+            source = err.source();
+        }
+        BuildError::Template {
+            path: path.to_path_buf(),
+            page,
+            message,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            BuildError::NotAnImage { .. } => Severity::Info,
+            BuildError::AmbiguousOrder { .. } | BuildError::EmptyFolder { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// The file or folder the problem is in.
+    pub fn path(&self) -> &Path {
+        match self {
+            BuildError::Config { path, .. }
+            | BuildError::InvalidSetting { path, .. }
+            | BuildError::BadSlug { path, .. }
+            | BuildError::MissingFolder { path, .. }
+            | BuildError::MissingFile { path, .. }
+            | BuildError::OutputFolder { path, .. }
+            | BuildError::EmptyFolder { path, .. }
+            | BuildError::OutsideFolder { path, .. }
+            | BuildError::NestedFolder { path }
+            | BuildError::NotAnImage { path, .. }
+            | BuildError::AmbiguousOrder { path, .. }
+            | BuildError::UnreadableImage { path, .. }
+            | BuildError::Thumbnail { path, .. }
+            | BuildError::Template { path, .. }
+            | BuildError::Copy { path, .. }
+            | BuildError::Io { path, .. } => path,
+        }
+    }
+
+    /// The line and column in the file, if they're known.
+    pub fn position(&self) -> Option<(usize, Option<usize>)> {
+        match self {
+            BuildError::Config {
+                line: Some(line),
+                column,
+                ..
+            }
+            | BuildError::InvalidSetting {
+                line: Some(line),
+                column,
+                ..
+            }
+            | BuildError::BadSlug {
+                line: Some(line),
+                column,
+                ..
+            } => Some((*line, *column)),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            BuildError::Config { .. } => "config",
+            BuildError::InvalidSetting { .. } => "invalid_setting",
+            BuildError::BadSlug { .. } => "bad_slug",
+            BuildError::OutputFolder { .. } => "output_folder",
+            BuildError::MissingFolder { .. } => "missing_folder",
+            BuildError::MissingFile { .. } => "missing_file",
+            BuildError::OutsideFolder { .. } => "outside_folder",
+            BuildError::EmptyFolder { .. } => "empty_folder",
+            BuildError::NestedFolder { .. } => "nested_folder",
+            BuildError::NotAnImage { .. } => "not_an_image",
+            BuildError::AmbiguousOrder { .. } => "ambiguous_order",
+            BuildError::UnreadableImage { .. } => "unreadable_image",
+            BuildError::Thumbnail { .. } => "thumbnail",
+            BuildError::Template { .. } => "template",
+            BuildError::Copy { .. } => "copy",
+            BuildError::Io { .. } => "io",
+        }
+    }
+
+    /// Where the problem is, like `input/config.toml:3:7`.
+    pub fn location(&self) -> String {
+        let path = self.path().display();
+        match self.position() {
+            Some((line, Some(column))) => format!("{}:{}:{}", path, line, column),
+            Some((line, None)) => format!("{}:{}", path, line),
+            None => path.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Config { path, message, .. } => {
+                write!(f, "Couldn't read {}: {}", path.display(), message)
+            }
+            BuildError::InvalidSetting { message, .. } | BuildError::BadSlug { message, .. } => {
+                f.write_str(message)
+            }
+            BuildError::OutputFolder { path, reason } => write!(
+                f,
+                "The output folder {:?} {}, so building could overwrite or remove it",
+                path, reason
+            ),
+            BuildError::MissingFolder { path, what } => {
+                write!(f, "{} {:?} is not a directory", what, path)
+            }
+            BuildError::MissingFile { path, what } => {
+                write!(f, "{} {:?} doesn't exist", what, path)
+            }
+            BuildError::OutsideFolder { path, what, reason } => {
+                write!(f, "{} {:?} {}, so it was left out", what, path, reason)
+            }
+            BuildError::EmptyFolder { path, what } => {
+                write!(f, "{} {:?} has no pages in it", what, path)
+            }
+            BuildError::NestedFolder { path } => write!(
+                f,
+                "Folder {:?} is inside a chapter, only one level of chapters is supported",
+                path
+            ),
+            BuildError::NotAnImage { path, reason } => {
+                write!(f, "Skipped {}, as {}", path.display(), reason)
+            }
+            BuildError::AmbiguousOrder { message, .. } => f.write_str(message),
+            BuildError::UnreadableImage { path, message } => {
+                write!(f, "Couldn't read page {}: {}", path.display(), message)
+            }
+            BuildError::Thumbnail {
+                comic,
+                path,
+                message,
+            } => write!(
+                f,
+                "Couldn't make a thumbnail for comic {} from {}: {}",
+                comic,
+                path.display(),
+                message
+            ),
+            BuildError::Template {
+                path,
+                page: Some(page),
+                message,
+            } => write!(
+                f,
+                "Couldn't render {} with {}: {}",
+                page,
+                path.display(),
+                message
+            ),
+            BuildError::Template {
+                path,
+                page: None,
+                message,
+            } => write!(
+                f,
+                "Couldn't parse the templates in {}: {}",
+                path.display(),
+                message
+            ),
+            BuildError::Copy {
+                from: Some(from),
+                path,
+                message,
+            } => write!(
+                f,
+                "Failed to copy {} to {}: {}",
+                from.display(),
+                path.display(),
+                message
+            ),
+            BuildError::Copy {
+                from: None,
+                path,
+                message,
+            } => write!(f, "Couldn't write {}: {}", path.display(), message),
+            BuildError::Io { path, message } => {
+                write!(f, "Couldn't read or write {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+impl From<BuildError> for io::Error {
+    fn from(err: BuildError) -> io::Error {
+        io::Error::other(err.to_string())
+    }
+}
+
+/// Every problem has the same shape in JSON, whatever its kind.
+impl Serialize for BuildError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            kind: &'static str,
+            severity: Severity,
+            message: String,
+            file: &'a Path,
+            line: Option<usize>,
+            column: Option<usize>,
+        }
+
+        let position = self.position();
+        Json {
+            kind: self.kind(),
+            severity: self.severity(),
+            message: self.to_string(),
+            file: self.path(),
+            line: position.map(|(line, _)| line),
+            column: position.and_then(|(_, column)| column),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The 1-based line and column of the first `key = ...` in a TOML file.
+pub(crate) fn locate_key(text: &str, key: &str) -> Option<(usize, usize)> {
+    text.lines().enumerate().find_map(|(i, line)| {
+        let rest = line.trim_start();
+        let after = rest.strip_prefix(key)?.trim_start();
+        if after.starts_with('=') {
+            Some((i + 1, line.len() - rest.len() + 1))
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,283 @@
+//! Finding the pages in comic and chapter folders, and putting them in order.
+
+use crate::{
+    check::locate,
+    config::{Config, ImportComic, Sort, DEFAULT_IGNORE},
+    dirs::Dirs,
+    error::BuildError,
+    images::detect_image,
+};
+use serde::Deserialize;
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The contents of an optional `chapter.toml` inside a chapter folder.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ChapterInfo {
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+}
+
+pub(crate) static CHAPTER_FILE: &str = "chapter.toml";
+
+/// Compiles the patterns for files that are never pages: the defaults and the
+/// config's `ignore`. Patterns that aren't valid are left out and returned as
+/// problems, located in the config's `text`.
+pub(crate) fn ignore_patterns(
+    dirs: &Dirs,
+    config: &Config,
+    text: &str,
+) -> (Vec<glob::Pattern>, Vec<BuildError>) {
+    let mut ignore = Vec::new();
+    for pattern in DEFAULT_IGNORE {
+        ignore.extend(glob::Pattern::new(pattern));
+    }
+    let mut problems = Vec::new();
+    for pattern in &config.ignore {
+        match glob::Pattern::new(pattern) {
+            Ok(pattern) => ignore.push(pattern),
+            Err(err) => {
+                let position = locate(text, "ignore", pattern, 0);
+                problems.push(BuildError::InvalidSetting {
+                    path: dirs.config.clone(),
+                    line: position.map(|(line, _)| line),
+                    column: position.map(|(_, column)| column),
+                    message: format!("Invalid ignore pattern {:?}: {}", pattern, err),
+                });
+            }
+        }
+    }
+    (ignore, problems)
+}
+
+pub(crate) fn is_ignored(root: &Path, path: &Path, ignore: &[glob::Pattern]) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.display().to_string().replace("\\", "/");
+    let name = file_name(path);
+    ignore
+        .iter()
+        .any(|pattern| pattern.matches(&relative) || pattern.matches(&name))
+}
+
+/// Lists the image files and the subfolders of a comic or chapter folder.
+/// Ignored entries are left out silently, other files that aren't images are
+/// recorded as `NotAnImage`.
+pub(crate) fn read_folder(
+    root: &Path,
+    folder: &Path,
+    ignore: &[glob::Pattern],
+    problems: &mut Vec<BuildError>,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut folders = Vec::new();
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(err) => {
+            problems.push(BuildError::io(folder, err));
+            return (files, folders);
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                problems.push(BuildError::io(folder, err));
+                continue;
+            }
+        };
+        if is_ignored(root, &path, ignore) {
+            continue;
+        }
+        if path.is_dir() {
+            folders.push(path);
+        } else if path.file_name() != Some(CHAPTER_FILE.as_ref()) {
+            match detect_image(&path) {
+                Ok(Ok(_)) => files.push(path),
+                Ok(Err(reason)) => problems.push(BuildError::NotAnImage { path, reason }),
+                Err(err) => problems.push(BuildError::UnreadableImage {
+                    path,
+                    message: err.to_string(),
+                }),
+            }
+        }
+    }
+    (files, folders)
+}
+
+/// A piece of a file name for natural ordering. Numbers sort before text, and
+/// compare by their value: by digit count once leading zeroes are removed,
+/// then digit by digit.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum NameChunk {
+    Number(usize, String),
+    Text(String),
+}
+
+pub(crate) fn natural_key(name: &str) -> Vec<NameChunk> {
+    let mut chunks = Vec::new();
+    let mut rest = name;
+    while let Some(first) = rest.chars().next() {
+        let is_digit = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(if is_digit {
+            let digits = chunk.trim_start_matches('0');
+            NameChunk::Number(digits.len(), digits.into())
+        } else {
+            NameChunk::Text(chunk.to_lowercase())
+        });
+        rest = tail;
+    }
+    chunks
+}
+
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    natural_key(a).cmp(&natural_key(b)).then_with(|| a.cmp(b))
+}
+
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Sorts one folder's entries, warning about neighbours that only ended up in
+/// their order by accident, like `page-1.png` and `page-01.png`.
+pub(crate) fn sort_paths(paths: &mut [PathBuf], sort: Sort, problems: &mut Vec<BuildError>) {
+    fn warn_ties<K: PartialEq>(
+        paths: &[PathBuf],
+        key: impl Fn(&Path) -> K,
+        why: &str,
+        problems: &mut Vec<BuildError>,
+    ) {
+        for pair in paths.windows(2) {
+            if key(&pair[0]) == key(&pair[1]) {
+                problems.push(BuildError::AmbiguousOrder {
+                    path: pair[1].clone(),
+                    message: format!(
+                        "{:?} and {:?} {}, so their order is ambiguous. \
+                         Rename one of them or list them in the comic's order.",
+                        pair[0], pair[1], why
+                    ),
+                });
+            }
+        }
+    }
+
+    let mtime = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match sort {
+        Sort::Name => paths.sort(),
+        Sort::Natural => {
+            paths.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b)));
+            warn_ties(
+                paths,
+                |path| natural_key(&file_name(path)),
+                "have the same number",
+                problems,
+            );
+        }
+        Sort::Mtime => {
+            paths.sort_by(|a, b| {
+                mtime(a)
+                    .cmp(&mtime(b))
+                    .then_with(|| natural_cmp(&file_name(a), &file_name(b)))
+            });
+            warn_ties(paths, mtime, "were modified at the same time", problems);
+        }
+    }
+}
+
+/// Puts the entries of one folder of a comic into reading order: anything
+/// listed in the comic's `order` first, then the rest by its `sort` setting.
+/// Only ties in the rest are warned about, as unlisted pages going after the
+/// listed ones is what `order` is documented to do.
+pub(crate) fn order_paths(
+    mut paths: Vec<PathBuf>,
+    comic: &ImportComic,
+    comic_folder: &Path,
+    problems: &mut Vec<BuildError>,
+) -> Vec<PathBuf> {
+    let mut ordered = Vec::new();
+    for entry in &comic.order {
+        let listed = comic_folder.join(entry);
+        if let Some(i) = paths.iter().position(|path| *path == listed) {
+            ordered.push(paths.remove(i));
+        }
+    }
+    sort_paths(&mut paths, comic.sort, problems);
+    ordered.extend(paths);
+    ordered
+}
+
+pub(crate) fn read_chapter_info(folder: &Path, problems: &mut Vec<BuildError>) -> ChapterInfo {
+    let path = folder.join(CHAPTER_FILE);
+    if !path.is_file() {
+        return ChapterInfo::default();
+    }
+    let result = fs::read_to_string(&path)
+        .map_err(|err| BuildError::io(&path, err))
+        .and_then(|text| {
+            toml::de::from_str(&text).map_err(|err| BuildError::toml_in(&path, &text, &err))
+        });
+    match result {
+        Ok(info) => info,
+        Err(err) => {
+            problems.push(err);
+            ChapterInfo::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("page-2", "page-10"), Ordering::Less);
+        assert_eq!(natural_cmp("page-10", "page-9"), Ordering::Greater);
+        assert_eq!(natural_cmp("2", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("page-99", "page-100"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeroes_dont_change_the_order() {
+        assert_eq!(natural_key("page-007"), natural_key("page-7"));
+        assert_eq!(natural_cmp("page-007", "page-10"), Ordering::Less);
+        // Equal keys still get a stable order from the plain names.
+        assert_eq!(natural_cmp("page-01", "page-1"), Ordering::Less);
+        assert_eq!(natural_key("0"), natural_key("000"));
+    }
+
+    #[test]
+    fn numbers_sort_before_text() {
+        assert_eq!(natural_cmp("1", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("cover", "1"), Ordering::Greater);
+        assert_eq!(natural_cmp("page", "page-1"), Ordering::Less);
+    }
+
+    #[test]
+    fn digits_at_the_end_are_numbers() {
+        assert_eq!(
+            natural_key("ch12"),
+            vec![
+                NameChunk::Text("ch".into()),
+                NameChunk::Number(2, "12".into())
+            ]
+        );
+        assert_eq!(natural_cmp("ch9", "ch12"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_is_ignored_until_it_decides_a_tie() {
+        assert_eq!(natural_key("Page-1"), natural_key("page-1"));
+        assert_eq!(natural_cmp("Page-2", "page-10"), Ordering::Less);
+        assert_eq!(natural_cmp("Page-1", "page-1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,305 @@
+//! Reading page images, and writing their resized copies and thumbnails.
+
+use crate::{
+    abs_path,
+    config::{ImageConfig, ThumbnailConfig},
+    error::BuildError,
+    outputs::{copy_file, Outputs},
+    site::ComicPage,
+};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    ColorType, DynamicImage,
+};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// A resized copy of a page, written next to the original in `output/images`.
+#[derive(Debug, Clone)]
+pub(crate) struct Derivative {
+    path: String,
+    width: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    Bmp,
+    Svg,
+}
+
+impl ImageFormat {
+    fn mime(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    fn from_extension(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        Some(match extension.as_str() {
+            "png" => ImageFormat::Png,
+            "jpg" | "jpeg" => ImageFormat::Jpeg,
+            "gif" => ImageFormat::Gif,
+            "webp" => ImageFormat::WebP,
+            "bmp" => ImageFormat::Bmp,
+            "svg" => ImageFormat::Svg,
+            _ => return None,
+        })
+    }
+
+    fn from_magic(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::WebP)
+        } else if bytes.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else {
+            let text = String::from_utf8_lossy(bytes);
+            let text = text.trim_start_matches('\u{feff}').trim_start();
+            if text.starts_with("<svg") || text.starts_with("<?xml") {
+                Some(ImageFormat::Svg)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Checks that a file is an image a browser can show, going by both its
+/// extension and its first few bytes. Returns why it isn't one otherwise.
+pub(crate) fn detect_image(path: &Path) -> io::Result<Result<ImageFormat, String>> {
+    if ImageFormat::from_extension(path).is_none() {
+        return Ok(Err("it doesn't have an image file extension".into()));
+    }
+    let mut bytes = Vec::with_capacity(64);
+    File::open(path)?.take(64).read_to_end(&mut bytes)?;
+    Ok(ImageFormat::from_magic(&bytes)
+        .ok_or_else(|| "it's named like an image, but its contents aren't an image".into()))
+}
+
+pub(crate) fn image_path(root: &Path, path: &Path) -> String {
+    Path::new("images")
+        .join(path.strip_prefix(root).unwrap())
+        .display()
+        .to_string()
+}
+
+/// Reads a page's size and works out which resized copies it needs. Pages only
+/// get copies narrower than the original. GIFs, WebPs and SVGs are left
+/// alone: WebPs can only be written losslessly, which would make copies of
+/// lossy ones bigger than the original.
+pub(crate) fn load_page(
+    root: &Path,
+    path: &Path,
+    images: &ImageConfig,
+    base_path: &str,
+) -> Result<ComicPage, String> {
+    let src = image_path(root, path);
+    let format = detect_image(path).map_err(|err| err.to_string())??;
+    let size = fs::metadata(path).map_err(|err| err.to_string())?.len();
+    let (width, height) = match format {
+        ImageFormat::Svg => (None, None),
+        _ => {
+            let reader = image::io::Reader::open(path)
+                .and_then(|reader| reader.with_guessed_format())
+                .map_err(|err| err.to_string())?;
+            let (width, height) = reader.into_dimensions().map_err(|err| err.to_string())?;
+            (Some(width), Some(height))
+        }
+    };
+    let extension = match format {
+        ImageFormat::Png | ImageFormat::Bmp => Some("png"),
+        ImageFormat::Jpeg => Some("jpg"),
+        _ => None,
+    };
+    let derive = |width: u32, extension: &str| {
+        let src = Path::new(&src);
+        let stem = src.file_stem().unwrap().to_string_lossy();
+        let name = format!("{}-{}w.{}", stem, width, extension);
+        Derivative {
+            path: src.with_file_name(name).display().to_string(),
+            width,
+        }
+    };
+    let srcset = |derivatives: &[Derivative]| {
+        derivatives
+            .iter()
+            .map(|d| format!("{} {}w", abs_path(base_path, &d.path), d.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut derivatives = Vec::new();
+    let mut page_srcset = String::new();
+    let mut webp_srcset = None;
+    if let (Some(extension), Some(full_width)) = (extension, width) {
+        let widths: Vec<_> = images
+            .widths
+            .iter()
+            .copied()
+            .filter(|&w| w > 0 && w < full_width)
+            .collect();
+        let mut resized: Vec<_> = widths.iter().map(|&w| derive(w, extension)).collect();
+        resized.push(Derivative {
+            path: src.clone(),
+            width: full_width,
+        });
+        page_srcset = srcset(&resized);
+        resized.pop();
+        derivatives.extend(resized);
+
+        // Lossless WebP only pays off for pages that were lossless already.
+        if images.webp && extension == "png" {
+            let webp: Vec<_> = widths
+                .iter()
+                .chain(Some(&full_width))
+                .map(|&w| derive(w, "webp"))
+                .collect();
+            webp_srcset = Some(srcset(&webp));
+            derivatives.extend(webp);
+        }
+    }
+
+    Ok(ComicPage {
+        src,
+        srcset: page_srcset,
+        webp_srcset,
+        width,
+        height,
+        size,
+        mime: format.mime(),
+        source: path.to_path_buf(),
+        derivatives,
+    })
+}
+
+pub(crate) fn load_pages(
+    root: &Path,
+    files: Vec<PathBuf>,
+    images: &ImageConfig,
+    base_path: &str,
+    problems: &mut Vec<BuildError>,
+) -> Vec<ComicPage> {
+    let mut pages = Vec::new();
+    for file in files {
+        match load_page(root, &file, images, base_path) {
+            Ok(page) => pages.push(page),
+            Err(message) => problems.push(BuildError::UnreadableImage {
+                path: file,
+                message,
+            }),
+        }
+    }
+    pages
+}
+
+/// Saves an image in the format matching the extension of `dst`.
+pub(crate) fn write_image(image: &DynamicImage, dst: &Path) -> Result<(), String> {
+    fs::create_dir_all(dst.parent().unwrap()).map_err(|err| err.to_string())?;
+    let file = io::BufWriter::new(File::create(dst).map_err(|err| err.to_string())?);
+    let extension = dst.extension().unwrap_or_default().to_string_lossy();
+    let result = match &extension[..] {
+        "jpg" => JpegEncoder::new_with_quality(file, 85).encode_image(&image.to_rgb8()),
+        "webp" => {
+            let image = image.to_rgba8();
+            WebPEncoder::new_lossless(file).encode(
+                &image,
+                image.width(),
+                image.height(),
+                ColorType::Rgba8,
+            )
+        }
+        _ => image.write_to(&mut { file }, image::ImageOutputFormat::Png),
+    };
+    result.map_err(|err| err.to_string())
+}
+
+/// Copies a page into `output/images` along with its resized copies, skipping
+/// any that are unchanged since the last build.
+pub(crate) fn publish_page(page: &ComicPage, outputs: &Outputs, problems: &mut Vec<BuildError>) {
+    copy_file(&page.source, &page.src, outputs, problems);
+    let hash = match outputs.hash_source(&page.source) {
+        Ok(hash) => hash,
+        Err(err) => {
+            problems.push(BuildError::UnreadableImage {
+                path: page.source.clone(),
+                message: err.to_string(),
+            });
+            return;
+        }
+    };
+    let mut image = None;
+    for derivative in &page.derivatives {
+        let key = format!("{}:{}w", hash, derivative.width);
+        if outputs.is_fresh(&derivative.path, &key) {
+            outputs.record(&derivative.path, key);
+            continue;
+        }
+        let dst = outputs.path(&derivative.path);
+        if image.is_none() {
+            let decoded = image::io::Reader::open(&page.source)
+                .map_err(|err| err.to_string())
+                .and_then(|reader| reader.with_guessed_format().map_err(|err| err.to_string()))
+                .and_then(|reader| reader.decode().map_err(|err| err.to_string()));
+            match decoded {
+                Ok(decoded) => image = Some(decoded),
+                Err(message) => {
+                    problems.push(BuildError::UnreadableImage {
+                        path: page.source.clone(),
+                        message,
+                    });
+                    return;
+                }
+            }
+        }
+        let resized =
+            image
+                .as_ref()
+                .unwrap()
+                .resize(derivative.width, u32::MAX, FilterType::Lanczos3);
+        match write_image(&resized, &dst) {
+            Ok(()) => outputs.record(&derivative.path, key),
+            Err(message) => problems.push(BuildError::Copy {
+                from: None,
+                path: dst,
+                message,
+            }),
+        }
+    }
+}
+
+/// Scales an image down to cover the thumbnail size, keeping the top of the
+/// image where a comic page usually starts, and saves it as a JPEG.
+pub(crate) fn make_thumbnail(src: &Path, dst: &Path, size: &ThumbnailConfig) -> Result<(), String> {
+    if size.width == 0 || size.height == 0 {
+        return Err("The thumbnail size has to be at least 1 pixel".into());
+    }
+    let image = image::open(src).map_err(|err| err.to_string())?;
+    let scale = f64::max(
+        f64::from(size.width) / f64::from(image.width()),
+        f64::from(size.height) / f64::from(image.height()),
+    );
+    let width = (f64::from(image.width()) * scale).ceil() as u32;
+    let height = (f64::from(image.height()) * scale).ceil() as u32;
+    let scaled = image.resize_exact(width, height, FilterType::Lanczos3);
+    let thumbnail = scaled.crop_imm((width - size.width) / 2, 0, size.width, size.height);
+    write_image(&thumbnail, dst)
+}
@@ -0,0 +1,560 @@
+//! Builds a comic website from the images and config in `input/`, using the
+//! templates in `templates/` and the files in `static/`.
+//!
+//! [`Site::load`] reads a site and [`Site::build`] builds it, which is what
+//! the `comic-publisher` program does. The [`publish`] module uploads the
+//! built site to GitHub Pages.
+
+mod check;
+pub mod cli;
+mod config;
+mod dirs;
+mod error;
+mod folders;
+mod images;
+mod markdown;
+mod outputs;
+pub mod publish;
+mod render;
+pub mod serve;
+mod site;
+
+pub use crate::{
+    config::{
+        Config, Format, GitHubConfig, ImageConfig, ImportChapter, ImportComic, ImportPage, Layout,
+        PathConfig, ServerConfig, Sort, ThumbnailConfig,
+    },
+    dirs::{DirOptions, Dirs},
+    error::{BuildError, Severity},
+    site::{BuildReport, Chapter, Comic, ComicPage, Page, Site},
+};
+use crate::{
+    dirs::resolve,
+    outputs::{read_manifest, remove_output, MANIFEST},
+};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    sync::mpsc,
+    time::Duration,
+};
+
+fn doc_text(text: &str) -> String {
+    if cfg!(windows) {
+        text.replace("\n", "\r\n")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Puts `base_path` in the form the rest of the build expects: the path's
+/// segments with no leading, trailing or repeated slashes, so `"/comic/"` and
+/// `"comic"` are the same, and `"/"` is the same as no base path.
+pub fn normalize_base_path(base_path: &str) -> String {
+    let segments: Vec<_> = base_path.split('/').filter(|s| !s.is_empty()).collect();
+    segments.join("/")
+}
+
+/// Strips `base_path` from the start of a URL path, returning the rest of it,
+/// or `None` if the path isn't under the base path. Only whole segments
+/// match, so base path `comic` doesn't match `/comics/`. The path to the base
+/// path itself without a trailing slash gives an empty string.
+pub fn strip_base_path<'a>(base_path: &str, path: &'a str) -> Option<&'a str> {
+    let base_path = normalize_base_path(base_path);
+    if base_path.is_empty() {
+        return Some(path);
+    }
+    let rest = path.strip_prefix('/')?.strip_prefix(base_path.as_str())?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn abs_path(root: &str, path: &str) -> String {
+    let root = normalize_base_path(root);
+    let path = path.trim_start_matches('/');
+    if root.is_empty() {
+        format!("/{}", path)
+    } else {
+        format!("/{}/{}", root, path)
+    }
+}
+
+/// Escapes text to go between HTML tags.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Settings for a build that don't come from the config file.
+#[derive(Debug, Default)]
+pub struct BuildOptions {
+    /// Leave files in `output/` that the build didn't produce.
+    pub keep_stale: bool,
+}
+
+/// Writes `README.txt` in the input folder, and if there's no input folder
+/// yet, an example config and comic folder. Returns whether the site is new,
+/// in which case there's nothing to build yet.
+pub fn init(dirs: &Dirs) -> io::Result<bool> {
+    let needs_init = !dirs.input.is_dir();
+    fs::create_dir_all(&dirs.input)?;
+    // Only rewritten when it differs, so watch mode doesn't see its own write.
+    let readme = doc_text(README);
+    let readme_path = dirs.input.join("README.txt");
+    if fs::read_to_string(&readme_path).ok().as_ref() != Some(&readme) {
+        let mut file = File::create(&readme_path)?;
+        file.write_all(readme.as_bytes())?;
+    }
+    if needs_init {
+        if !dirs.config.exists() {
+            let mut config = File::create(&dirs.config)?;
+            config.write_all(doc_text(CONFIG).as_bytes())?;
+        }
+        fs::create_dir(dirs.input.join("comic"))?;
+    }
+    Ok(needs_init)
+}
+
+/// Reads the config and checks it without building anything: that the
+/// folders and files it names exist and have pages in them, that no two pages
+/// or comics end up at the same URL, and that `base_path` can be used in one.
+/// Returns the problems found, which point at the line they're on when they
+/// can.
+pub fn check(dirs: &Dirs) -> Result<Vec<BuildError>, BuildError> {
+    check::check(dirs)
+}
+
+/// Removes everything the last build put in the output folder, going by its
+/// manifest, and the errors file. Anything else in the folder is left alone.
+/// Returns how many files were removed.
+pub fn clean(dirs: &Dirs) -> io::Result<usize> {
+    if dirs.errors.is_file() {
+        fs::remove_file(&dirs.errors)?;
+    }
+    let manifest = match read_manifest(&dirs.output) {
+        Some(manifest) => manifest,
+        None => return Ok(0),
+    };
+    let mut removed = 0;
+    for path in manifest.outputs.keys() {
+        if dirs.output.join(path).is_file() {
+            remove_output(&dirs.output, path)?;
+            removed += 1;
+        }
+    }
+    fs::remove_file(dirs.output.join(MANIFEST))?;
+    if fs::read_dir(&dirs.output)?.next().is_none() {
+        fs::remove_dir(&dirs.output)?;
+    }
+    Ok(removed)
+}
+
+/// Calls `rebuild` with the files that changed whenever something the site is
+/// built from changes. Only returns if watching fails.
+pub fn watch(dirs: &Dirs, mut rebuild: impl FnMut(&[String])) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(300)).map_err(io::Error::other)?;
+    for path in dirs.watched() {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(io::Error::other)?;
+    }
+
+    // The errors file is written by builds, so changes to it are left out,
+    // or a build with errors would start the next one.
+    let errors = resolve(&dirs.errors);
+    let cwd = env::current_dir()?;
+    loop {
+        // Wait for a change, then take everything else that's queued up so a
+        // burst of saves only causes one rebuild.
+        let mut changed = Vec::new();
+        while changed.is_empty() {
+            let mut events = vec![rx.recv().map_err(io::Error::other)?];
+            events.extend(rx.try_iter());
+            for event in events {
+                let path = match event {
+                    DebouncedEvent::Create(path)
+                    | DebouncedEvent::Write(path)
+                    | DebouncedEvent::Remove(path)
+                    | DebouncedEvent::Rename(_, path) => path,
+                    DebouncedEvent::Rescan => PathBuf::from("(everything)"),
+                    DebouncedEvent::Error(err, _) => {
+                        println!("Error watching for changes: {}", err);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if resolve(&path) == errors {
+                    continue;
+                }
+                let path = path
+                    .strip_prefix(&cwd)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        rebuild(&changed);
+    }
+}
+
+/// Loads the site in `dirs` and builds it.
+pub fn build(dirs: &Dirs, options: &BuildOptions) -> Result<BuildReport, BuildError> {
+    Site::from_dirs(dirs.clone())?.build(options)
+}
+
+/// Writes the report's errors to the errors file, or removes it if there
+/// aren't any. The file is only rewritten when the errors are different.
+pub fn write_errors(dirs: &Dirs, report: &BuildReport) -> io::Result<()> {
+    if report.has_errors() {
+        let mut text = String::new();
+        for error in report.errors() {
+            text.push_str(&format!(
+                "{}: {}: {}\n",
+                error.location(),
+                error.severity(),
+                error
+            ));
+        }
+        if fs::read_to_string(&dirs.errors).ok().as_ref() != Some(&text) {
+            fs::write(&dirs.errors, text)?;
+        }
+    } else if dirs.errors.is_file() {
+        fs::remove_file(&dirs.errors)?;
+    }
+    Ok(())
+}
+
+static CONFIG: &str = r#"title = "Comic Website"
+copyright = "Copyright &copy; 2019"
+
+[[pages]]
+page = "about"
+title = "About"
+content = """
+This is a demo comics website!
+You can put just whatever in here!
+"""
+
+[[comics]]
+folder = "comic"
+title = "First Comic"
+description = """
+This is just an example description.
+You can write them on multiple lines like this if you use 3 quotes like this.
+"""
+"#;
+
+static README: &str = r#"How to use this tool.
+
+Make a folder for each comic inside this "input" folder, with the pages
+inside. Put your pages inside the folder and name your pages in
+alphabetical order. An easy way to do this is just number them all,
+for example page-1.png or 1-pagetitle.png. Numbers are put in counting
+order, so page-2.png comes before page-10.png without needing page-02.png.
+
+Edit the config.toml file. You should be able to use any text editor on your
+computer. You want to make one entry for each comic and each page. They will
+be listed on the site in the order you put them here.
+
+The main file has several pieces of information describing the site as a whole.
+- title: The title of the site, displayed at the top of the page.
+- author: (optional) The site's author. This is currently unused.
+- copyright: (optional) The copyright to include in the footer of the site.
+  You can put any HTML you want in here, and it will be included verbatim.
+  A good default is simply "Copyright &copy; <Your Name>".
+- base_path: (optional) The base path of the website. You can leave this out
+  if you're publishing your website at the root of a domain, like for instance
+  directly at example.com. If you'll be publishing at example.com/comic
+  however, you need to set the base_path to "comic". If you're going to publish
+  on GitHub, you should make this the same as your repo name.
+- thumbnails: (optional) The size of the thumbnails made for comics, in
+  pixels. The default is:
+      [thumbnails]
+      width = 480
+      height = 400
+  Pages are scaled down to cover this size, and cropped to keep the top.
+- images: (optional) Smaller copies of each page are made so phones don't
+  have to download the full size image. The default is:
+      [images]
+      widths = [480, 960, 1600]
+      webp = false
+      sizes = "(max-width: 800px) 100vw, 800px"
+  A copy is made at each width that's smaller than the page, except for GIF
+  and WebP pages, which are used as they are. Setting webp to true also makes
+  lossless WebP copies of PNG pages, which are usually smaller. sizes tells
+  browsers how wide pages are shown on the site; you only need to change it
+  if you change the layout in style.css.
+- ignore: (optional) A list of files to leave out of comic folders, like
+  ignore = ["*.psd", "comic/drafts"]. A * matches any part of a name.
+  Hidden files, Thumbs.db and desktop.ini are always left out, and any other
+  file that isn't a PNG, JPEG, GIF, WebP, BMP or SVG image is skipped and
+  listed when building.
+- server: (optional) Where the server program makes the site available
+  while you preview it. For example:
+      [server]
+      host = "0.0.0.0"
+      port = 8080
+  The host is 127.0.0.1 by default, which only lets this computer see the
+  site; 0.0.0.0 lets other devices on your network, like your phone, see it
+  too. Without a port, the server uses 8888, or the next free port if that's
+  taken. You can also pick these when starting the server, with --host and
+  --port. The server prints every address the site can be opened at.
+  Starting it with --production-headers as well makes it send the same
+  caching and compression headers as GitHub Pages, and serve the pages just
+  as they're built, so they don't reload by themselves.
+- paths: (optional) Where the site's folders are, if they aren't next to
+  each other in one folder. Paths are relative to the folder this file is
+  in. For example:
+      [paths]
+      input = "."
+      output = "../site"
+      templates = "../templates"
+      static = "../static"
+      errors = "errors.txt"
+  Each of them can also be given when running the programs, like
+  --output ../site, which takes priority over this.
+
+Pages have these parts.
+- page: Determines what the url will be. If the page is "about", then you'll
+  have a url like example.com/about. Every page and comic needs its own url,
+  and two that only differ in capital letters count as the same. Nothing
+  can go inside a comic's url, like "comic/about" for a comic "comic".
+- title: How the page is labelled everywhere.
+- content: Used to fill in the page. You can put any HTML you want in the
+  content, including just writing some plain text.
+- content_file: (optional) A file in the input folder to fill in the page
+  with instead of content, like "about.md".
+- format: (optional) What the content is written in, "html" or "markdown".
+  Markdown lets you write paragraphs, *emphasis*, [links](https://example.com),
+  lists and tables without any HTML. The default is "markdown" when the
+  content_file ends in .md, and "html" otherwise.
+
+If you make your own templates, the markdown filter turns Markdown into HTML
+in them too, like {{ some_text | markdown | safe }}.
+
+Comic entries have these parts.
+- folder: Determines the url of the comic, and where to find the folder of images.
+- thumbnail: (optional) An image to use as the comic's thumbnail on the home
+  page, used exactly as it is. If you leave this out, a thumbnail is made for
+  you from the first page of the comic.
+- thumbnail_page: (optional) The page to make the thumbnail from instead of
+  the first one, like "page-3.png" or "chapter-2/page-1.png".
+- title: How the comic is labelled on the home page and the comic page.
+- description: A description for the comic, shown on the comic page.
+- format: (optional) What the description and the chapters' descriptions are
+  written in, "html" (the default) or "markdown".
+- layout: (optional) How readers move through the comic. The default is
+  "paged", which gives every page its own url, like example.com/comic/3, with
+  first, previous, next and last links. The arrow keys also move between
+  pages, and example.com/comic/latest always shows the newest page.
+  Set it to "scroll" to show every page stacked on one long page instead.
+
+If a comic is long, you can split it into chapters by putting folders inside
+the comic's folder, with that chapter's pages inside. Each chapter gets its own
+page listing its pages, at a url like example.com/comic/chapter/part-1 for a
+folder named part-1, and the previous and next links carry on from one chapter
+into the next. Any pages directly in the comic's folder come before the first
+chapter, at example.com/comic/1 and on.
+
+A chapter is named after its folder unless you give it a title. You can do
+that either by putting a chapter.toml file inside the chapter folder:
+
+    title = "The Beginning"
+    description = "Where it all starts."
+
+or by listing the chapter under the comic in config.toml:
+
+    [[comics.chapters]]
+    folder = "chapter-1"
+    title = "The Beginning"
+    description = "Where it all starts."
+
+Comics can also choose how their pages are ordered.
+- sort: (optional) "natural" is the default, and orders pages by name with
+  numbers in counting order. "name" orders them strictly letter by letter,
+  like page-10.png before page-2.png. "mtime" orders them by when each file
+  was last changed, oldest first.
+- order: (optional) A list of pages to put first, in exactly the order
+  listed. Pages in chapters are written with the chapter folder, like
+  order = ["cover.png", "chapter-1/intro.png"]. Anything not listed goes
+  after the listed pages, sorted as usual.
+If two pages could go in either order, like page-1.png and page-01.png,
+you'll see a warning when building.
+
+Here's a full example that has multiple comics and a page listed:
+
+    title = "A Comics Site"
+    author = "Cassie Jones"
+    copyright = "Copyright &copy; 2019 Cassie Jones"
+    base_path = "a-comics-site"
+
+    [[pages]]
+    page = "about"
+    title = "About"
+    content = """
+    This is a demo comics website!
+    You can put just whatever in here!
+    """
+
+    [[comics]]
+    folder = "comic"
+    title = "First Comic"
+    description = """
+    This is the description for the first comic.
+    You can write it with multiple lines if you have 3 quotes like that.
+    """
+
+    [[comics]]
+    folder = "comic2"
+    thumbnail = "thumbnails/comic2.png"
+    title = "Second Comic"
+    description = """
+    This example isn't in the default.
+    """
+
+Once this is set up, every time your run the program, it will build
+your comic into the output folder. Only the files affected by what you
+changed since the last build are redone, and files that are no longer part
+of the site are removed, so don't put anything else in the output folder.
+If you'd rather keep them, run the program with --keep-stale. What was built
+is kept track of in output/.build-manifest.json; delete it to redo everything.
+Pages are worked on several at a time, using all of your computer's
+processors. To use fewer, run the program with --jobs and a number, for
+example --jobs 2.
+
+While you're working on your comic, you can run the program with --watch.
+It keeps running and rebuilds the site whenever you change anything in the
+input, templates or static folders, printing any errors as it goes.
+
+You can also preview your site with the server program, which builds the
+site itself and does the same, so you don't need to run this one. Open the
+address it shows in your browser. Pages reload by themselves after each
+rebuild, and any errors are shown on the page.
+
+If you'd like to upload your comic via github, add a [github] section
+in the file. For example:
+
+    [github]
+    username = "<your github name>"
+    repository = "a-comics-site"
+    domain = "comic.example.com"
+    author = "<your name here>"
+    email = "you@example.com"
+
+- username: Your GitHub username.
+- repository: The repository name that the comic will be uploaded to.
+  This should not be a repository that anything else will be uploaded to.
+- domain: (optional) The custom domain that you want the website to be on.
+  If you leave this out, it will be published to the default URL at
+  http://<username>.github.io/<repository>
+- author: (optional) The author name to attribute the git commits to.
+- email: (optional) The email to attribute the git commits to.
+  This should probably match the email you use for GitHub.
+
+If you're comfortable with the command line, the comic-publisher program
+does everything the others do, and a bit more:
+- comic-publisher init: Makes the input folder, like running comics the
+  first time.
+- comic-publisher build: The same as running comics. Add --report json or
+  --report html to get what it did and the problems it found in those
+  formats.
+- comic-publisher serve: The same as running server.
+- comic-publisher publish: The same as running github-upload.
+- comic-publisher check: Looks for problems in config.toml without building
+  anything: misspelled settings, pages or comics with the same name, comic
+  folders with no pages in them and the like. Each problem says which line
+  of config.toml it's on.
+- comic-publisher clean: Removes everything the build put in the output
+  folder.
+They all take --input, --output and --config to use folders and a config
+file other than these, and --quiet to only print errors. The comics, server
+and github-upload programs take these too. With just --config, the config
+file's folder is used as the input folder, and the output, templates and
+static folders are looked for next to it.
+"#;
+
+/// An empty folder for a test to work in.
+#[cfg(test)]
+pub(crate) fn test_folder(name: &str) -> PathBuf {
+    let folder = env::temp_dir().join(format!("comics-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn base_path_is_normalized() {
+        assert_eq!(normalize_base_path(""), "");
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("comic"), "comic");
+        assert_eq!(normalize_base_path("/comic"), "comic");
+        assert_eq!(normalize_base_path("comic/"), "comic");
+        assert_eq!(normalize_base_path("/comic/"), "comic");
+        assert_eq!(normalize_base_path("//my//comic//"), "my/comic");
+    }
+
+    #[test]
+    fn base_path_matches_whole_segments() {
+        assert_eq!(strip_base_path("comic", "/comic/1/"), Some("/1/"));
+        assert_eq!(strip_base_path("comic", "/comic/"), Some("/"));
+        assert_eq!(strip_base_path("comic", "/comic"), Some(""));
+        assert_eq!(strip_base_path("comic", "/comics-archive/1/"), None);
+        assert_eq!(strip_base_path("comic", "/comics"), None);
+        assert_eq!(strip_base_path("comic", "/other/comic/"), None);
+        assert_eq!(strip_base_path("comic", "/"), None);
+        assert_eq!(strip_base_path("my/comic", "/my/comic/1"), Some("/1"));
+        assert_eq!(strip_base_path("my/comic", "/my/comics/1"), None);
+    }
+
+    #[test]
+    fn base_path_slashes_dont_matter() {
+        for base_path in &["comic", "/comic", "comic/", "/comic/"] {
+            assert_eq!(strip_base_path(base_path, "/comic/1/"), Some("/1/"));
+            assert_eq!(strip_base_path(base_path, "/comics/1/"), None);
+            assert_eq!(abs_path(base_path, "style.css"), "/comic/style.css");
+            assert_eq!(abs_path(base_path, "/"), "/comic/");
+        }
+    }
+
+    #[test]
+    fn empty_base_path_matches_everything() {
+        for base_path in &["", "/"] {
+            assert_eq!(strip_base_path(base_path, "/"), Some("/"));
+            assert_eq!(strip_base_path(base_path, "/comic/1/"), Some("/comic/1/"));
+            assert_eq!(abs_path(base_path, "/style.css"), "/style.css");
+            assert_eq!(abs_path(base_path, "/"), "/");
+        }
+    }
+
+    #[test]
+    fn the_documented_configs_work() {
+        let path = Path::new("config.toml");
+        Config::parse(path, CONFIG).unwrap();
+        let example: String = README
+            .lines()
+            .skip_while(|line| !line.starts_with("Here's a full example"))
+            .skip(1)
+            .take_while(|line| line.is_empty() || line.starts_with("    "))
+            .map(|line| format!("{}\n", line.trim_start_matches("    ")))
+            .collect();
+        let config = Config::parse(path, &example).unwrap();
+        assert_eq!(config.author.as_deref(), Some("Cassie Jones"));
+        assert_eq!(config.comics.len(), 2);
+    }
+}
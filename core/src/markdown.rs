@@ -0,0 +1,40 @@
+//! Turning Markdown in the config, content files and templates into HTML.
+
+use pulldown_cmark::{html, Options, Parser};
+use std::collections::HashMap;
+
+/// Renders CommonMark, with tables, footnotes and strikethrough, to HTML.
+pub(crate) fn to_html(text: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(text, options));
+    out
+}
+
+/// The `markdown` filter for templates, like `{{ text | markdown | safe }}`.
+pub(crate) fn markdown_filter(
+    value: &tera::Value,
+    _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("The markdown filter only works on strings"))?;
+    Ok(to_html(text).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_has_tables_and_footnotes() {
+        let text = "Hi *there*[^1]\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n[^1]: Note";
+        let html = to_html(text);
+        assert!(html.contains("<em>there</em>"));
+        assert!(html.contains("<table>"));
+        assert!(html.contains("class=\"footnote-definition\""));
+    }
+}
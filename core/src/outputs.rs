@@ -0,0 +1,313 @@
+//! Tracking what each build writes to the output folder, so unchanged files
+//! are skipped and stale ones removed.
+
+use crate::{error::BuildError, site::BuildReport};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+/// Kept in the output folder.
+pub(crate) static MANIFEST: &str = ".build-manifest.json";
+
+/// A record of what the last build put in `output/`, so the next build can
+/// skip anything that wouldn't change.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub(crate) struct Manifest {
+    /// Hashes of input files, reused while their size and mtime are the same.
+    pub(crate) sources: BTreeMap<String, Source>,
+    /// A key for every output file, which changes whenever its contents would.
+    pub(crate) outputs: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub(crate) struct Source {
+    size: u64,
+    modified: SystemTime,
+    hash: String,
+}
+
+/// Tracks the files written by this build against the last build's manifest.
+/// It's shared between the threads of the build, so it locks internally.
+pub(crate) struct Outputs {
+    dir: PathBuf,
+    old: Manifest,
+    new: Mutex<Manifest>,
+    pub(crate) templates: String,
+    added: AtomicUsize,
+    changed: AtomicUsize,
+    unchanged: AtomicUsize,
+}
+
+pub(crate) fn hash(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.input((part.len() as u64).to_le_bytes());
+        hasher.input(part);
+    }
+    format!("{:x}", hasher.result())
+}
+
+/// Hashes the names and contents of every file in a folder.
+pub(crate) fn hash_folder(folder: &Path) -> io::Result<String> {
+    fn visit(folder: &Path, parts: &mut Vec<Vec<u8>>) -> io::Result<()> {
+        let mut entries = fs::read_dir(folder)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                visit(&path, parts)?;
+            } else {
+                parts.push(path.display().to_string().into_bytes());
+                parts.push(fs::read(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    let mut parts = Vec::new();
+    visit(folder, &mut parts)?;
+    Ok(hash(
+        &parts.iter().map(|part| &part[..]).collect::<Vec<_>>(),
+    ))
+}
+
+impl Outputs {
+    pub(crate) fn load(dir: &Path, templates: String) -> Outputs {
+        let old = read_manifest(dir).unwrap_or_default();
+        Outputs {
+            dir: dir.to_path_buf(),
+            old,
+            new: Mutex::default(),
+            templates,
+            added: AtomicUsize::new(0),
+            changed: AtomicUsize::new(0),
+            unchanged: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn hash_source(&self, path: &Path) -> io::Result<String> {
+        let name = path.display().to_string();
+        if let Some(source) = self.new.lock().unwrap().sources.get(&name) {
+            return Ok(source.hash.clone());
+        }
+        let meta = fs::metadata(path)?;
+        let (size, modified) = (meta.len(), meta.modified()?);
+        let hash = match self.old.sources.get(&name) {
+            Some(old) if old.size == size && old.modified == modified => old.hash.clone(),
+            _ => hash(&[&fs::read(path)?]),
+        };
+        self.new.lock().unwrap().sources.insert(
+            name,
+            Source {
+                size,
+                modified,
+                hash: hash.clone(),
+            },
+        );
+        Ok(hash)
+    }
+
+    /// Where `path` goes in the output folder.
+    pub(crate) fn path(&self, path: &str) -> PathBuf {
+        self.dir.join(path)
+    }
+
+    /// Whether `path` in `output/` already holds what `key` describes.
+    pub(crate) fn is_fresh(&self, path: &str, key: &str) -> bool {
+        self.old.outputs.get(path).map(|old| &old[..]) == Some(key) && self.path(path).is_file()
+    }
+
+    pub(crate) fn record(&self, path: &str, key: String) {
+        let count = match self.old.outputs.get(path) {
+            None => &self.added,
+            Some(old) if *old != key => &self.changed,
+            Some(_) => &self.unchanged,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        self.new
+            .lock()
+            .unwrap()
+            .outputs
+            .insert(path.to_string(), key);
+    }
+
+    /// Removes the files the last build made that this one didn't, unless
+    /// `keep_stale`, and saves the manifest. Only files in the last build's
+    /// manifest are ever removed, so anything else in the output folder, like
+    /// a `.git` folder, is left alone, and a first build removes nothing.
+    /// Nothing is removed if this build had errors either, since those files
+    /// may have only been missed because of the errors. What changed is
+    /// added to the report.
+    pub(crate) fn finish(self, report: &mut BuildReport, keep_stale: bool) -> io::Result<()> {
+        let new = self.new.into_inner().unwrap();
+        let dir = &self.dir;
+        let stale: Vec<_> = self
+            .old
+            .outputs
+            .keys()
+            .filter(|path| !new.outputs.contains_key(*path) && dir.join(path).is_file())
+            .cloned()
+            .collect();
+        let remove = !report.has_errors() && !keep_stale;
+        if remove {
+            for path in &stale {
+                remove_output(&self.dir, path)?;
+            }
+        }
+        fs::write(self.dir.join(MANIFEST), serde_json::to_string(&new)?)?;
+
+        report.stale = stale.iter().map(|path| dir.join(path)).collect();
+        report.added = self.added.into_inner();
+        report.changed = self.changed.into_inner();
+        report.unchanged = self.unchanged.into_inner();
+        report.removed_stale = remove;
+        report.finished = true;
+        Ok(())
+    }
+}
+
+pub(crate) fn read_manifest(dir: &Path) -> Option<Manifest> {
+    let text = fs::read_to_string(dir.join(MANIFEST)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Removes a file the build made in the output folder `dir`, and the folders
+/// it was in if that leaves them empty. Paths that aren't plainly inside
+/// `dir` are skipped, in case the manifest was edited.
+pub(crate) fn remove_output(dir: &Path, path: &str) -> io::Result<()> {
+    let path = Path::new(path);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Ok(());
+    }
+    fs::remove_file(dir.join(path))?;
+    for folder in path.ancestors().skip(1) {
+        if folder.as_os_str().is_empty() {
+            break;
+        }
+        let folder = dir.join(folder);
+        if fs::read_dir(&folder)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(&folder)?;
+    }
+    Ok(())
+}
+
+/// Copies `src` to `path` inside `output/`, unless it's unchanged.
+pub(crate) fn copy_file(src: &Path, path: &str, outputs: &Outputs, problems: &mut Vec<BuildError>) {
+    let key = match outputs.hash_source(src) {
+        Ok(hash) => hash,
+        Err(err) => {
+            problems.push(BuildError::io(src, err));
+            return;
+        }
+    };
+    if outputs.is_fresh(path, &key) {
+        outputs.record(path, key);
+        return;
+    }
+    let dst = outputs.path(path);
+    let dir = dst.parent().unwrap();
+    if let Err(err) = fs::create_dir_all(dir) {
+        problems.push(BuildError::io(dir, err));
+    }
+    match fs::copy(src, &dst) {
+        Ok(_) => outputs.record(path, key),
+        Err(err) => problems.push(BuildError::Copy {
+            from: Some(src.to_path_buf()),
+            path: dst,
+            message: err.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_folder;
+
+    /// An output folder whose last build made `made`, with `others` in it too.
+    fn output_folder(name: &str, made: &[&str], others: &[&str]) -> PathBuf {
+        let dir = test_folder(name).join("output");
+        for path in made.iter().chain(others) {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        if !made.is_empty() {
+            let manifest = Manifest {
+                sources: BTreeMap::new(),
+                outputs: made
+                    .iter()
+                    .map(|path| (path.to_string(), "key".into()))
+                    .collect(),
+            };
+            fs::write(
+                dir.join(MANIFEST),
+                serde_json::to_string(&manifest).unwrap(),
+            )
+            .unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn a_first_build_removes_nothing() {
+        let dir = output_folder("first-build", &[], &["old.html", ".git/config"]);
+        let mut report = BuildReport::default();
+        Outputs::load(&dir, String::new())
+            .finish(&mut report, false)
+            .unwrap();
+        assert!(report.stale.is_empty());
+        assert!(dir.join("old.html").is_file());
+        assert!(dir.join(".git/config").is_file());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn only_paths_inside_the_output_folder_are_removed() {
+        let absolute = test_folder("outside-paths").join("absolute.txt");
+        let absolute = absolute.display().to_string();
+        let made = ["comic/old/index.html", "../outside.txt", &absolute];
+        let dir = output_folder("outside-paths", &made, &[]);
+        let folder = dir.parent().unwrap().to_path_buf();
+        let mut report = BuildReport::default();
+        Outputs::load(&dir, String::new())
+            .finish(&mut report, false)
+            .unwrap();
+        assert!(report.removed_stale);
+        assert!(!dir.join("comic").exists());
+        assert!(folder.join("outside.txt").is_file());
+        assert!(Path::new(&absolute).is_file());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_removed_after_errors() {
+        let dir = output_folder("after-errors", &["old.html"], &[]);
+        let mut report = BuildReport::from(BuildError::MissingFolder {
+            path: "input/comic".into(),
+            what: "Comic folder".into(),
+        });
+        Outputs::load(&dir, String::new())
+            .finish(&mut report, false)
+            .unwrap();
+        assert!(!report.removed_stale);
+        assert_eq!(report.stale, [dir.join("old.html")]);
+        assert!(dir.join("old.html").is_file());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+//! Rendering the templates into the pages of the site.
+
+use crate::{
+    abs_path,
+    config::{Config, Layout},
+    error::BuildError,
+    images::{make_thumbnail, publish_page},
+    outputs::{copy_file, hash, Outputs},
+    site::{Chapter, Comic, Page, ThumbnailSource},
+};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{collections::HashMap, error::Error, fs, io, path::Path};
+use tera::Tera;
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Navigation {
+    first: String,
+    prev: Option<String>,
+    next: Option<String>,
+    last: String,
+    latest: String,
+    prev_chapter: Option<String>,
+    next_chapter: Option<String>,
+}
+
+/// What reader pages get of their comic and chapter: everything but the
+/// pages. Each reader page's context is hashed on every build to tell if it
+/// changed, so it can't grow with the comic.
+#[derive(Serialize, Debug)]
+struct ReaderComic<'a> {
+    title: &'a str,
+    thumbnail: &'a Option<String>,
+    url: &'a str,
+    description: &'a str,
+    layout: Layout,
+    chapters: Vec<ReaderChapter<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct ReaderChapter<'a> {
+    title: &'a str,
+    description: &'a str,
+    url: &'a str,
+}
+
+impl<'a> ReaderChapter<'a> {
+    fn new(chapter: &'a Chapter) -> Self {
+        ReaderChapter {
+            title: &chapter.title,
+            description: &chapter.description,
+            url: &chapter.url,
+        }
+    }
+}
+
+pub(crate) fn write_html(path: &Path, html: &str) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, html)
+}
+
+/// Renders a template into `index.html` in each of `dirs`, unless the
+/// templates and the context are the same as last time. `page` says what's
+/// being rendered, for errors.
+pub(crate) fn render(
+    tera: &Tera,
+    template: &str,
+    page: &str,
+    context: tera::Context,
+    dirs: &[&str],
+    outputs: &Outputs,
+) -> Result<(), BuildError> {
+    let paths: Vec<_> = dirs
+        .iter()
+        .map(|dir| {
+            Path::new(dir)
+                .join("index.html")
+                .display()
+                .to_string()
+                .replace("\\", "/")
+        })
+        .collect();
+    render_to(tera, template, page, context, &paths, outputs)
+}
+
+/// Renders a template into each of `paths` in the output folder, unless the
+/// templates and the context are the same as last time.
+pub(crate) fn render_to(
+    tera: &Tera,
+    template: &str,
+    page: &str,
+    context: tera::Context,
+    paths: &[String],
+    outputs: &Outputs,
+) -> Result<(), BuildError> {
+    let template_error = |err: &dyn Error| {
+        let path = tera
+            .get_template(template)
+            .ok()
+            .and_then(|t| t.path.clone())
+            .unwrap_or_else(|| template.to_string());
+        BuildError::template(Path::new(&path), Some(page.to_string()), err)
+    };
+    let json =
+        serde_json::to_string(&context.clone().into_json()).map_err(|err| template_error(&err))?;
+    let key = hash(&[
+        outputs.templates.as_bytes(),
+        template.as_bytes(),
+        json.as_bytes(),
+    ]);
+    if !paths.iter().all(|path| outputs.is_fresh(path, &key)) {
+        let html = tera
+            .render(template, context)
+            .map_err(|err| template_error(&err))?;
+        for path in paths {
+            let dst = outputs.path(path);
+            write_html(&dst, &html).map_err(|err| BuildError::Copy {
+                from: None,
+                path: dst,
+                message: err.to_string(),
+            })?;
+        }
+    }
+    for path in paths {
+        outputs.record(path, key.clone());
+    }
+    Ok(())
+}
+
+pub(crate) fn make_path(root: String) -> impl tera::Function {
+    move |args: &HashMap<String, tera::Value>| {
+        let path = args
+            .get("path")
+            .ok_or_else(|| tera::Error::msg("Missing parameter `path`"))?
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("Expected `path` to be a string"))?;
+        Ok(abs_path(&root, path).into())
+    }
+}
+
+/// The context every template gets.
+pub(crate) fn base_context(config: &Config, pages: &[Page]) -> tera::Context {
+    let mut context = tera::Context::new();
+    context.insert("pages", pages);
+    context.insert("title", &config.title);
+    context.insert("copyright", &config.copyright);
+    context
+}
+
+/// Writes a comic's images and pages to `output/`, with the pages and images
+/// done in parallel. Returns the problems in the order they'd happen in one
+/// by one, so builds report the same thing each time.
+pub(crate) fn publish_comic(
+    comic: &Comic,
+    tera: &Tera,
+    config: &Config,
+    pages: &[Page],
+    outputs: &Outputs,
+) -> Vec<BuildError> {
+    let mut problems = Vec::new();
+    match (&comic.thumbnail, &comic.thumbnail_source) {
+        (Some(thumbnail), Some(ThumbnailSource::Page(source))) => {
+            let size = &config.thumbnails;
+            let result = outputs.hash_source(source).map_err(|err| err.to_string());
+            let result = result.and_then(|hash| {
+                let key = format!("thumbnail:{}:{}x{}", hash, size.width, size.height);
+                if !outputs.is_fresh(thumbnail, &key) {
+                    make_thumbnail(source, &outputs.path(thumbnail), size)?;
+                }
+                outputs.record(thumbnail, key);
+                Ok(())
+            });
+            if let Err(message) = result {
+                problems.push(BuildError::Thumbnail {
+                    comic: comic.title.clone(),
+                    path: source.clone(),
+                    message,
+                });
+            }
+        }
+        (Some(thumbnail), Some(ThumbnailSource::File(source))) => {
+            copy_file(source, thumbnail, outputs, &mut problems)
+        }
+        _ => (),
+    }
+    let page_problems: Vec<_> = comic
+        .pages
+        .par_iter()
+        .map(|page| {
+            let mut problems = Vec::new();
+            publish_page(page, outputs, &mut problems);
+            problems
+        })
+        .collect();
+    problems.extend(page_problems.into_iter().flatten());
+
+    // Pages outside of any chapter come first, numbered from the comic's
+    // url, followed by each chapter numbered from the chapter's url.
+    let loose = comic.pages.len() - comic.chapters.iter().map(|c| c.pages.len()).sum::<usize>();
+    let mut groups = vec![(None, comic.url.as_str(), &comic.pages[..loose])];
+    for (i, chapter) in comic.chapters.iter().enumerate() {
+        groups.push((Some(i), chapter.url.as_str(), &chapter.pages[..]));
+    }
+    let mut entries = Vec::new();
+    for (chapter, url, pages) in groups {
+        for (i, page) in pages.iter().enumerate() {
+            entries.push((
+                chapter,
+                format!("{}/{}", url, i + 1),
+                i + 1,
+                pages.len(),
+                page,
+            ));
+        }
+    }
+    let latest = format!("{}/latest", comic.url);
+
+    let mut context = base_context(config, pages);
+    context.insert("comic", &comic);
+    context.insert("sizes", &config.images.sizes);
+    context.insert("first_page", &entries.first().map(|entry| &entry.1));
+    context.insert("latest_page", &latest);
+
+    let page = format!("comic {}", &comic.title);
+    if let Err(err) = render(tera, "comic.html", &page, context, &[&comic.url], outputs) {
+        problems.push(err);
+    }
+
+    for chapter in &comic.chapters {
+        let mut context = base_context(config, pages);
+        context.insert("comic", &comic);
+        context.insert("sizes", &config.images.sizes);
+        context.insert("chapter", chapter);
+
+        let page = format!("chapter {} of comic {}", &chapter.title, &comic.title);
+        if let Err(err) = render(
+            tera,
+            "chapter.html",
+            &page,
+            context,
+            &[&chapter.url],
+            outputs,
+        ) {
+            problems.push(err);
+        }
+    }
+
+    if comic.layout == Layout::Scroll {
+        return problems;
+    }
+
+    let chapter_url = |i: usize| comic.chapters.get(i).map(|c| c.url.clone());
+    let reader_comic = ReaderComic {
+        title: &comic.title,
+        thumbnail: &comic.thumbnail,
+        url: &comic.url,
+        description: &comic.description,
+        layout: comic.layout,
+        chapters: comic.chapters.iter().map(ReaderChapter::new).collect(),
+    };
+    let reader_problems: Vec<_> = entries
+        .par_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let (chapter, url, number, count, page) = entry;
+            let nav = Navigation {
+                first: entries[0].1.clone(),
+                prev: i.checked_sub(1).map(|i| entries[i].1.clone()),
+                next: entries.get(i + 1).map(|entry| entry.1.clone()),
+                last: entries[entries.len() - 1].1.clone(),
+                latest: latest.clone(),
+                prev_chapter: chapter.and_then(|c| c.checked_sub(1)).and_then(chapter_url),
+                next_chapter: chapter_url(chapter.map_or(0, |c| c + 1)),
+            };
+
+            let mut context = base_context(config, pages);
+            context.insert("comic", &reader_comic);
+            context.insert("sizes", &config.images.sizes);
+            context.insert("chapter", &chapter.map(|c| &reader_comic.chapters[c]));
+            context.insert("page", page);
+            context.insert("number", number);
+            context.insert("count", count);
+            context.insert("nav", &nav);
+            context.insert("canonical", url);
+
+            let dirs: &[&str] = if i + 1 == entries.len() {
+                &[url, &latest]
+            } else {
+                &[url]
+            };
+            let page = format!("page {} of comic {}", url, &comic.title);
+            render(tera, "reader.html", &page, context, dirs, outputs).err()
+        })
+        .collect();
+    problems.extend(reader_problems.into_iter().flatten());
+    problems
+}
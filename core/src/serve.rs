@@ -0,0 +1,666 @@
+use crate::{escape_html, BuildOptions, Config, Dirs};
+use flate2::{write::GzEncoder, Compression};
+use futures::{future, stream, sync::mpsc, Future, Stream};
+use hyper::{
+    self, header,
+    server::{conn::AddrIncoming, Builder},
+    service::Service,
+    Body, Method, Response, StatusCode,
+};
+use hyper_staticfile::Static;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{mpsc as std_mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Settings for the preview server that don't come from the config file.
+#[derive(Debug, Default)]
+pub struct ServeOptions {
+    pub dirs: Dirs,
+    /// The address to listen on, instead of the one in the config or
+    /// 127.0.0.1.
+    pub host: Option<IpAddr>,
+    /// The port to listen on, instead of the one in the config or the first
+    /// free one from 8888.
+    pub port: Option<u16>,
+    /// Send the same headers as the static host the site is published to,
+    /// and serve pages exactly as they're built.
+    pub production_headers: bool,
+    /// Only print errors.
+    pub quiet: bool,
+}
+
+static DEFAULT_PORT: u16 = 8888;
+
+/// How many ports after the default to try before giving up.
+static PORT_ATTEMPTS: u16 = 100;
+
+/// Where pages listen for changes to the site.
+static RELOAD_PATH: &str = "/__reload";
+
+/// Added to the end of every HTML page, so it reloads when the site changes.
+static RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/__reload").onmessage = function () { location.reload(); };
+</script>
+"#;
+
+/// The pages currently waiting to be told to reload.
+type Listeners = Arc<Mutex<Vec<mpsc::UnboundedSender<()>>>>;
+
+/// The result of the latest build: the errors it collected, or the error that
+/// stopped it. Locked while the site is being rebuilt, so requests wait for
+/// the new version.
+type LastBuild = Arc<Mutex<Result<Vec<String>, String>>>;
+
+struct Server {
+    root: String,
+    output: PathBuf,
+    server: Static,
+    listeners: Listeners,
+    last_build: LastBuild,
+    production: bool,
+}
+
+impl Server {
+    /// Keeps the connection open as an event stream, sending an event each
+    /// time `output/` changes.
+    fn listen(&self) -> Response<Body> {
+        let (tx, rx) = mpsc::unbounded();
+        self.listeners.lock().unwrap().push(tx);
+        let events = stream::once(Ok(": listening\n\n"))
+            .chain(rx.map(|()| "data: reload\n\n"))
+            .map_err(|()| io::Error::other("reload events stopped"));
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::wrap_stream(events))
+            .unwrap()
+    }
+}
+
+/// Builds the site, printing a summary of what went wrong.
+fn build(dirs: &Dirs, quiet: bool) -> Result<Vec<String>, String> {
+    let report = crate::build(dirs, &BuildOptions::default()).map_err(|err| {
+        println!("Error: {}", err);
+        err.to_string()
+    })?;
+    crate::write_errors(dirs, &report).map_err(|err| err.to_string())?;
+    report.print(quiet);
+    let errors: Vec<_> = report
+        .errors()
+        .map(|error| format!("{}: {}", error.location(), error))
+        .collect();
+    if !errors.is_empty() {
+        println!(
+            "There were {} errors, they're shown in the browser.",
+            errors.len()
+        );
+    }
+    Ok(errors)
+}
+
+/// A box listing build errors, to show on top of the page.
+fn error_list(errors: &[String]) -> String {
+    let mut html = String::from(
+        "<div style=\"position: fixed; top: 0; left: 0; right: 0; z-index: 1000; \
+         max-height: 50vh; overflow: auto; padding: 0 1em; background: #fee; \
+         color: #900; border-bottom: 2px solid #900; font: 14px monospace;\">\n\
+         <p><strong>The site was built with errors:</strong></p>\n<ul>\n",
+    );
+    for error in errors {
+        html.push_str(&format!(
+            "<li style=\"white-space: pre-wrap\">{}</li>\n",
+            escape_html(error)
+        ));
+    }
+    html.push_str("</ul>\n</div>\n");
+    html
+}
+
+/// Shown instead of a page when the site couldn't be built, or the page is
+/// missing and the build had errors.
+fn error_page(errors: &[String]) -> Response<Body> {
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Build errors</title>\n</head>\n<body>\n{}{}</body>\n</html>\n",
+        error_list(errors),
+        RELOAD_SCRIPT
+    );
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(html))
+        .unwrap()
+}
+
+/// The site's `404.html`, if it has one, with a 404 status.
+fn not_found(output: &Path) -> Option<Response<Body>> {
+    let html = fs::read(output.join("404.html")).ok()?;
+    let res = Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CONTENT_LENGTH, html.len())
+        .body(Body::from(html))
+        .unwrap();
+    Some(res)
+}
+
+/// Where a request is found in the output folder, with the base path taken off and
+/// the query kept. Requests outside the base path, and for the base path
+/// without a trailing slash, get the location to redirect them to instead.
+fn site_uri(root: &str, uri: &hyper::Uri) -> Result<hyper::Uri, String> {
+    let with_query = |path: &str| match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    match crate::strip_base_path(root, uri.path()) {
+        Some("") => Err(with_query(&format!("/{}/", root))),
+        Some(path) => Ok(hyper::Uri::builder()
+            .path_and_query(with_query(path).as_str())
+            .build()
+            .unwrap()),
+        None => Err(with_query(&format!("/{}{}", root, uri.path()))),
+    }
+}
+
+/// Sends requests for paths outside the base path to the same path under it,
+/// as that's where the site is when published.
+fn redirect(uri: &hyper::Uri, location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, location)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(format!("{} is at {}\n", uri.path(), location)))
+        .unwrap()
+}
+
+/// Adds the reload script, and the build errors if there are any, to an HTML
+/// page.
+fn inject(body: &[u8], errors: &[String]) -> Vec<u8> {
+    let mut html = String::from_utf8_lossy(body).into_owned();
+    let end = html.rfind("</body>").unwrap_or(html.len());
+    html.insert_str(end, RELOAD_SCRIPT);
+    if !errors.is_empty() {
+        html.insert_str(end, &error_list(errors));
+    }
+    html.into_bytes()
+}
+
+/// The type of the files the site is made of, by extension.
+fn content_type(path: &str) -> Option<&'static str> {
+    if path.ends_with('/') {
+        return Some("text/html; charset=utf-8");
+    }
+    let extension = path.rsplit('.').next()?.to_ascii_lowercase();
+    let content_type = match &extension[..] {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "application/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// Whether a response is worth compressing.
+fn is_text(res: &Response<Body>) -> bool {
+    let content_type = match res.headers().get(header::CONTENT_TYPE) {
+        Some(content_type) => content_type.to_str().unwrap_or(""),
+        None => return false,
+    };
+    content_type.starts_with("text/")
+        || content_type.starts_with("application/javascript")
+        || content_type.starts_with("application/json")
+        || content_type.starts_with("application/xml")
+        || content_type.starts_with("image/svg+xml")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The best encoding the browser accepts. Our static host doesn't do
+    /// brotli, so it isn't used with production headers.
+    fn negotiate(accept: Option<&header::HeaderValue>, production: bool) -> Option<Encoding> {
+        let accept = accept?.to_str().ok()?;
+        let accepts = |name: &str| {
+            accept.split(',').any(|part| {
+                let mut params = part.split(';').map(str::trim);
+                params
+                    .next()
+                    .is_some_and(|coding| coding.eq_ignore_ascii_case(name))
+                    && params.all(|param| match param.strip_prefix("q=") {
+                        Some(q) => q.parse::<f32>().is_ok_and(|q| q > 0.0),
+                        None => true,
+                    })
+            })
+        };
+        if !production && accepts("br") {
+            Some(Encoding::Brotli)
+        } else if accepts("gzip") {
+            Some(Encoding::Gzip)
+        } else {
+            None
+        }
+    }
+
+    fn compress(self, body: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::new();
+        match self {
+            Encoding::Brotli => {
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(body)?;
+            }
+            Encoding::Gzip => {
+                let mut writer = GzEncoder::new(&mut compressed, Compression::default());
+                writer.write_all(body)?;
+                writer.finish()?;
+            }
+        }
+        Ok(compressed)
+    }
+}
+
+/// The parts of a request needed to finish its response.
+struct Conditions {
+    get: bool,
+    if_none_match: Option<header::HeaderValue>,
+    if_modified_since: Option<header::HeaderValue>,
+    encoding: Option<Encoding>,
+    production: bool,
+}
+
+impl Conditions {
+    /// Whether the browser's copy is still current. An ETag is checked if the
+    /// browser sent one, otherwise the modification date.
+    fn is_fresh(&self, headers: &header::HeaderMap) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            let etag = match headers
+                .get(header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+            {
+                Some(etag) => etag.trim_start_matches("W/"),
+                None => return false,
+            };
+            let if_none_match = if_none_match.to_str().unwrap_or("");
+            return if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag);
+        }
+        match (&self.if_modified_since, headers.get(header::LAST_MODIFIED)) {
+            (Some(since), Some(modified)) => since == modified,
+            _ => false,
+        }
+    }
+
+    /// Adds the caching headers, answers conditional requests, and compresses
+    /// text. HTML pages also get the reload script and the build errors when
+    /// `errors` is given, which changes their ETag to match.
+    fn finish(
+        self,
+        mut res: Response<Body>,
+        errors: Option<Vec<String>>,
+    ) -> Box<dyn Future<Item = Response<Body>, Error = io::Error> + Send> {
+        let headers = res.headers_mut();
+        if self.production {
+            // What GitHub Pages sends.
+            headers.insert(header::CACHE_CONTROL, "max-age=600".parse().unwrap());
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
+        } else {
+            headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+        }
+
+        if !self.get || !is_text(&res) || !res.status().is_success() && errors.is_none() {
+            if res.status() == StatusCode::OK && self.is_fresh(res.headers()) {
+                return Box::new(future::ok(not_modified(res)));
+            }
+            return Box::new(future::ok(res));
+        }
+
+        let (parts, body) = res.into_parts();
+        Box::new(
+            body.concat2()
+                .map_err(io::Error::other)
+                .and_then(move |body| {
+                    let mut res = Response::from_parts(parts, ());
+                    let body = match &errors {
+                        Some(errors) => {
+                            // The page isn't the file anymore, so its date doesn't
+                            // say whether it changed.
+                            res.headers_mut().remove(header::LAST_MODIFIED);
+                            inject(&body, errors)
+                        }
+                        None => body.to_vec(),
+                    };
+                    let hash = format!("{:x}", Sha256::digest(&body));
+                    let etag = format!("W/\"{}\"", &hash[..16]);
+                    let headers = res.headers_mut();
+                    headers.insert(header::ETAG, etag.parse().unwrap());
+                    headers.insert(header::VARY, "Accept-Encoding".parse().unwrap());
+                    if res.status() == StatusCode::OK && self.is_fresh(res.headers()) {
+                        return Ok(not_modified(res.map(|()| Body::empty())));
+                    }
+
+                    let body = match self.encoding {
+                        Some(encoding) => {
+                            let headers = res.headers_mut();
+                            headers
+                                .insert(header::CONTENT_ENCODING, encoding.name().parse().unwrap());
+                            encoding.compress(&body)?
+                        }
+                        None => body,
+                    };
+                    res.headers_mut()
+                        .insert(header::CONTENT_LENGTH, body.len().into());
+                    Ok(res.map(|()| Body::from(body)))
+                }),
+        )
+    }
+}
+
+/// Tells the browser to use its copy, keeping the headers that describe it.
+fn not_modified(res: Response<Body>) -> Response<Body> {
+    let (mut parts, _) = res.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_ENCODING);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// Tells every listening page to reload whenever the output folder changes,
+/// or an event is sent to the returned channel. Changes are collected until
+/// things have been quiet for a moment, so a rebuild only reloads the pages
+/// once.
+fn watch_output(
+    output: &Path,
+    listeners: Listeners,
+) -> notify::Result<std_mpsc::Sender<DebouncedEvent>> {
+    let (tx, rx) = std_mpsc::channel();
+    let mut watcher = notify::watcher(tx.clone(), Duration::from_millis(100))?;
+    watcher.watch(output, RecursiveMode::Recursive)?;
+    thread::spawn(move || {
+        let _watcher = watcher;
+        while rx.recv().is_ok() {
+            while rx.recv_timeout(Duration::from_millis(300)).is_ok() {}
+            listeners
+                .lock()
+                .unwrap()
+                .retain(|listener| listener.unbounded_send(()).is_ok());
+        }
+    });
+    Ok(tx)
+}
+
+impl Service for Server {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = Response<Body>, Error = io::Error> + Send>;
+
+    fn call(&mut self, mut req: hyper::Request<Self::ReqBody>) -> Self::Future {
+        // HEAD gets what GET would without the body, so the headers are the
+        // same, like the ETag of a page with the reload script added.
+        if req.method() == Method::HEAD {
+            *req.method_mut() = Method::GET;
+            return Box::new(self.call(req).map(|res| res.map(|_| Body::empty())));
+        }
+        if req.uri().path() == RELOAD_PATH {
+            return Box::new(future::ok(self.listen()));
+        }
+        let uri = match site_uri(&self.root, req.uri()) {
+            Ok(uri) => uri,
+            Err(location) => return Box::new(future::ok(redirect(req.uri(), &location))),
+        };
+        let get = req.method() == Method::GET;
+        let content_type = content_type(uri.path());
+        let html = get && content_type.is_some_and(|t| t.starts_with("text/html"));
+        *req.uri_mut() = uri;
+        let errors = match &*self.last_build.lock().unwrap() {
+            Ok(errors) => errors.clone(),
+            Err(err) if html => return Box::new(future::ok(error_page(std::slice::from_ref(err)))),
+            Err(_) => Vec::new(),
+        };
+        let conditions = Conditions {
+            get,
+            // Conditional requests are answered once the response is
+            // finished, as the page may not be the file as it is on disk.
+            if_none_match: req.headers_mut().remove(header::IF_NONE_MATCH),
+            if_modified_since: req.headers_mut().remove(header::IF_MODIFIED_SINCE),
+            encoding: Encoding::negotiate(
+                req.headers().get(header::ACCEPT_ENCODING),
+                self.production,
+            ),
+            production: self.production,
+        };
+        // Production headers mean serving the site just as it's published.
+        let inject_errors = if self.production {
+            None
+        } else {
+            Some(errors.clone())
+        };
+        let root = self.root.clone();
+        let output = self.output.clone();
+        Box::new(self.server.call(req).and_then(move |mut res| {
+            match res.status() {
+                StatusCode::OK => {
+                    if let Some(content_type) = content_type {
+                        let content_type = header::HeaderValue::from_static(content_type);
+                        res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+                    }
+                    if html {
+                        return conditions.finish(res, inject_errors);
+                    }
+                }
+                StatusCode::NOT_FOUND if html && !errors.is_empty() => {
+                    return Box::new(future::ok(error_page(&errors)));
+                }
+                StatusCode::NOT_FOUND => {
+                    if let Some(res) = not_found(&output) {
+                        return conditions.finish(res, inject_errors);
+                    }
+                }
+                // Folders are redirected to add a trailing slash, but the
+                // location has to be under the base path too.
+                StatusCode::MOVED_PERMANENTLY if !root.is_empty() => {
+                    let location = res.headers()[header::LOCATION].to_str().unwrap_or("/");
+                    let location = format!("/{}{}", root, location);
+                    if let Ok(location) = header::HeaderValue::from_str(&location) {
+                        res.headers_mut().insert(header::LOCATION, location);
+                    }
+                }
+                _ => (),
+            }
+            conditions.finish(res, None)
+        }))
+    }
+}
+
+/// Binds to the given port, or if none was asked for, the first free one
+/// from the default on.
+fn bind(host: IpAddr, port: Option<u16>) -> Result<Builder<AddrIncoming>, String> {
+    if let Some(port) = port {
+        let addr = SocketAddr::new(host, port);
+        return hyper::Server::try_bind(&addr)
+            .map_err(|err| format!("couldn't listen on {}: {}", addr, err));
+    }
+    (DEFAULT_PORT..DEFAULT_PORT + PORT_ATTEMPTS)
+        .find_map(|port| hyper::Server::try_bind(&SocketAddr::new(host, port)).ok())
+        .ok_or_else(|| {
+            format!(
+                "couldn't find a free port from {} to {}",
+                DEFAULT_PORT,
+                DEFAULT_PORT + PORT_ATTEMPTS - 1
+            )
+        })
+}
+
+/// Every URL the site can be reached at from this computer or, when
+/// listening on all addresses, from other devices on the network.
+fn urls(addr: SocketAddr, root: &str) -> Vec<String> {
+    let path = if root.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", root)
+    };
+    let url = |ip: IpAddr| {
+        if ip.is_loopback() {
+            format!("http://localhost:{}/{}", addr.port(), path)
+        } else {
+            format!("http://{}/{}", SocketAddr::new(ip, addr.port()), path)
+        }
+    };
+    if !addr.ip().is_unspecified() {
+        return vec![url(addr.ip())];
+    }
+    let mut ips: Vec<_> = get_if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|interface| interface.ip())
+        .filter(|ip| ip.is_ipv4() == addr.is_ipv4() && !ip.is_loopback())
+        .collect();
+    ips.sort();
+    ips.dedup();
+    let mut urls = vec![url(IpAddr::V4(Ipv4Addr::LOCALHOST))];
+    urls.extend(ips.into_iter().map(url));
+    urls
+}
+
+/// Builds the site and serves it, rebuilding it whenever it changes. Only
+/// returns if the server couldn't be started.
+pub fn serve(options: ServeOptions) -> Result<(), Box<dyn Error>> {
+    let dirs = options.dirs.clone();
+    crate::init(&dirs)?;
+    let config = Config::load(&dirs.config)?;
+    let root = crate::normalize_base_path(&config.base_path);
+    let quiet = options.quiet;
+    let last_build = Arc::new(Mutex::new(build(&dirs, quiet)));
+    let listeners = Listeners::default();
+    let reload = match watch_output(&dirs.output, listeners.clone()) {
+        Ok(reload) => Some(reload),
+        Err(err) => {
+            eprintln!("Pages won't reload when the site changes: {}", err);
+            None
+        }
+    };
+    // Rebuild as soon as anything changes, holding the lock so pages that are
+    // requested in the meantime wait for the new version.
+    let rebuilding = last_build.clone();
+    let watched = dirs.clone();
+    thread::spawn(move || {
+        let result = crate::watch(&watched, |changed| {
+            if !quiet {
+                println!("Rebuilding after changes to:");
+                for path in changed {
+                    println!("  {}", path);
+                }
+            }
+            let mut last_build = rebuilding.lock().unwrap();
+            *last_build = build(&watched, quiet);
+            // The errors shown may have changed even if no files did.
+            if let Some(reload) = &reload {
+                let _ = reload.send(DebouncedEvent::Rescan);
+            }
+        });
+        if let Err(err) = result {
+            eprintln!("The site won't be rebuilt when it changes: {}", err);
+        }
+    });
+    let host = options
+        .host
+        .or(config.server.host)
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let port = options.port.or(config.server.port);
+    let urls_root = root.clone();
+    let server = bind(host, port)?.serve(move || {
+        future::ok::<_, io::Error>(Server {
+            root: root.clone(),
+            output: dirs.output.clone(),
+            server: Static::new(&dirs.output),
+            listeners: listeners.clone(),
+            last_build: last_build.clone(),
+            production: options.production_headers,
+        })
+    });
+    let urls = urls(server.local_addr(), &urls_root);
+    let server = server.map_err(|e| eprintln!("Server error: {}", e));
+    eprintln!("Hosting your website at {}", urls[0]);
+    for url in &urls[1..] {
+        eprintln!("                    and {}", url);
+    }
+    hyper::rt::run(server);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site_path(root: &str, uri: &str) -> Result<String, String> {
+        site_uri(root, &uri.parse().unwrap()).map(|uri| uri.to_string())
+    }
+
+    #[test]
+    fn base_path_is_taken_off() {
+        assert_eq!(site_path("comic", "/comic/1/"), Ok("/1/".into()));
+        assert_eq!(
+            site_path("comic", "/comic/style.css"),
+            Ok("/style.css".into())
+        );
+        assert_eq!(site_path("", "/comic/1/"), Ok("/comic/1/".into()));
+    }
+
+    #[test]
+    fn query_is_kept() {
+        assert_eq!(
+            site_path("comic", "/comic/1/?page=2"),
+            Ok("/1/?page=2".into())
+        );
+        assert_eq!(site_path("", "/1/?page=2"), Ok("/1/?page=2".into()));
+        assert_eq!(
+            site_path("comic", "/1/?page=2"),
+            Err("/comic/1/?page=2".into())
+        );
+        assert_eq!(
+            site_path("comic", "/comic?page=2"),
+            Err("/comic/?page=2".into())
+        );
+    }
+
+    #[test]
+    fn paths_outside_base_path_redirect() {
+        assert_eq!(site_path("comic", "/"), Err("/comic/".into()));
+        assert_eq!(site_path("comic", "/comic"), Err("/comic/".into()));
+        assert_eq!(
+            site_path("comic", "/comics-archive/1/"),
+            Err("/comic/comics-archive/1/".into())
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! A site's content, loaded from its config and input folder, and the build
+//! that turns it into a website.
+
+use crate::{
+    check::{check_chapters, check_sizes, check_slugs},
+    config::{Config, ImportComic, ImportPage, Layout},
+    dirs::{join_inside, DirOptions, Dirs},
+    error::{BuildError, Severity},
+    escape_html,
+    folders::{ignore_patterns, order_paths, read_chapter_info, read_folder},
+    images::{image_path, load_pages, Derivative},
+    markdown::markdown_filter,
+    normalize_base_path,
+    outputs::{copy_file, hash_folder, Outputs},
+    render::{base_context, make_path, publish_comic, render, render_to},
+    BuildOptions,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tera::Tera;
+
+/// A comic as the templates see it.
+#[derive(Serialize, Debug)]
+pub struct Comic {
+    pub title: String,
+    pub thumbnail: Option<String>,
+    #[serde(skip)]
+    pub(crate) thumbnail_source: Option<ThumbnailSource>,
+    pub url: String,
+    pub description: String,
+    pub layout: Layout,
+    pub pages: Vec<ComicPage>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Where a comic's thumbnail comes from.
+#[derive(Debug)]
+pub(crate) enum ThumbnailSource {
+    /// A hand-made thumbnail, copied as it is.
+    File(PathBuf),
+    /// The page to make the thumbnail from, if it wasn't given one.
+    Page(PathBuf),
+}
+
+/// A subfolder of a comic's folder, with its own pages.
+#[derive(Serialize, Debug)]
+pub struct Chapter {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    pub pages: Vec<ComicPage>,
+}
+
+/// One page of a comic. `src` is the original image, while `srcset` and
+/// `webp_srcset` list the resized copies with the base path already applied,
+/// ready to use in a `srcset` attribute. The size and type describe the
+/// original, and the dimensions are missing for SVGs.
+#[derive(Serialize, Debug, Clone)]
+pub struct ComicPage {
+    pub src: String,
+    pub srcset: String,
+    pub webp_srcset: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub size: u64,
+    pub mime: &'static str,
+    #[serde(skip)]
+    pub source: PathBuf,
+    #[serde(skip)]
+    pub(crate) derivatives: Vec<Derivative>,
+}
+
+/// A page of text, like an about page.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Page {
+    pub page: String,
+    pub title: String,
+    pub content: String,
+}
+
+/// A site ready to be built: its config, and the comics found in its input
+/// folder.
+#[derive(Debug)]
+pub struct Site {
+    pub dirs: Dirs,
+    pub config: Config,
+    pub pages: Vec<Page>,
+    pub comics: Vec<Comic>,
+    /// Problems found while loading, which are passed on to the build report.
+    pub problems: Vec<BuildError>,
+}
+
+/// What a build did, and what went wrong.
+#[derive(Serialize, Debug, Default)]
+pub struct BuildReport {
+    /// Everything worth mentioning, in the order it was found.
+    pub problems: Vec<BuildError>,
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    /// Files in the output folder that aren't part of the site anymore.
+    pub stale: Vec<PathBuf>,
+    /// Whether the stale files were removed. They're kept if there were
+    /// errors, or if the build was asked to keep them.
+    pub removed_stale: bool,
+    /// Whether the build got to the end, rather than stopping at a problem
+    /// it couldn't get past.
+    pub finished: bool,
+}
+
+/// The report of a build that stopped before it got anywhere.
+impl From<BuildError> for BuildReport {
+    fn from(err: BuildError) -> BuildReport {
+        BuildReport {
+            problems: vec![err],
+            ..BuildReport::default()
+        }
+    }
+}
+
+impl BuildReport {
+    /// The problems that left something out of the site.
+    pub fn errors(&self) -> impl Iterator<Item = &BuildError> {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity() == Severity::Error)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// What happened, one problem to a line with where it is, ending with how
+    /// many files changed.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for problem in &self.problems {
+            text.push_str(&format!(
+                "{}: {}: {}\n",
+                problem.location(),
+                problem.severity(),
+                problem
+            ));
+        }
+        if !self.finished {
+            return text;
+        }
+        text.push_str(&format!(
+            "Built the site: {} files added, {} changed, {} removed, {} unchanged.\n",
+            self.added,
+            self.changed,
+            if self.removed_stale {
+                self.stale.len()
+            } else {
+                0
+            },
+            self.unchanged
+        ));
+        if !self.stale.is_empty() {
+            text.push_str(&format!(
+                "{} {} files that aren't part of the site anymore:\n",
+                if self.removed_stale {
+                    "Removed"
+                } else {
+                    "Kept"
+                },
+                self.stale.len()
+            ));
+            for path in &self.stale {
+                text.push_str(&format!("  {}\n", path.display()));
+            }
+        }
+        text
+    }
+
+    /// Prints the report, or only its errors.
+    pub fn print(&self, errors_only: bool) {
+        if errors_only {
+            for error in self.errors() {
+                println!("{}: {}: {}", error.location(), error.severity(), error);
+            }
+        } else {
+            print!("{}", self.text());
+        }
+    }
+
+    /// The report as JSON, for other tools to read.
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The report as a web page.
+    pub fn html(&self) -> String {
+        let mut rows = String::new();
+        for problem in &self.problems {
+            rows.push_str(&format!(
+                "<tr class=\"{}\"><td>{}</td><td><code>{}</code></td><td>{}</td></tr>\n",
+                escape_html(&format!("{:?}", problem.severity()).to_lowercase()),
+                problem.severity(),
+                escape_html(&problem.location()),
+                escape_html(&problem.to_string()),
+            ));
+        }
+        let problems = if rows.is_empty() {
+            "<p>No problems found.</p>\n".to_string()
+        } else {
+            format!(
+                "<table>\n<tr><th>Severity</th><th>File</th><th>Problem</th></tr>\n{}</table>\n",
+                rows
+            )
+        };
+        let mut stale = String::new();
+        for path in &self.stale {
+            let path = escape_html(&path.display().to_string());
+            stale.push_str(&format!("<li><code>{}</code></li>\n", path));
+        }
+        if !stale.is_empty() {
+            stale =
+                format!(
+                "<p>{} these files, which aren't part of the site anymore:</p>\n<ul>\n{}</ul>\n",
+                if self.removed_stale { "Removed" } else { "Kept" },
+                stale
+            );
+        }
+        let summary = if self.finished {
+            format!(
+                "{} errors. {} files added, {} changed, {} unchanged.",
+                self.errors().count(),
+                self.added,
+                self.changed,
+                self.unchanged
+            )
+        } else {
+            "The build stopped before it could finish.".to_string()
+        };
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Build report</title>\n<style>{}</style>\n</head>\n<body>\n\
+             <h1>Build report</h1>\n<p>{}</p>\n{}{}</body>\n</html>\n",
+            REPORT_STYLE, summary, problems, stale
+        )
+    }
+}
+
+static REPORT_STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+    table { border-collapse: collapse; } \
+    td, th { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; } \
+    .error td:first-child { color: #900; } .warning td:first-child { color: #960; }";
+
+impl Site {
+    /// Loads the site whose config file is at `path`, with the folders its
+    /// `[paths]` section gives.
+    pub fn load(path: impl AsRef<Path>) -> Result<Site, BuildError> {
+        Site::from_dirs(Dirs::load(DirOptions {
+            config: Some(path.as_ref().to_path_buf()),
+            ..DirOptions::default()
+        })?)
+    }
+
+    /// Loads the site in `dirs`. Only fails if the config can't be read;
+    /// problems with the comics are collected in `problems`.
+    pub fn from_dirs(dirs: Dirs) -> Result<Site, BuildError> {
+        let text =
+            fs::read_to_string(&dirs.config).map_err(|err| BuildError::io(&dirs.config, err))?;
+        let mut config = Config::parse(&dirs.config, &text)?;
+        let mut problems = check_slugs(&dirs, &config, &text);
+        problems.extend(check_sizes(&dirs, &config, &text));
+        config.base_path = normalize_base_path(&config.base_path);
+        let mut site = Site {
+            dirs,
+            pages: Vec::new(),
+            comics: Vec::new(),
+            problems,
+            config,
+        };
+        let (ignore, invalid) = ignore_patterns(&site.dirs, &site.config, &text);
+        site.problems.extend(invalid);
+        let Site {
+            dirs,
+            config,
+            pages,
+            comics,
+            problems,
+        } = &mut site;
+        for page in &config.pages {
+            pages.push(Page {
+                page: page.page.clone(),
+                title: page.title.clone(),
+                content: page
+                    .format()
+                    .to_html(&page_content(&dirs.input, page, problems)),
+            });
+        }
+        for comic in &config.comics {
+            comics.extend(load_comic(&dirs.input, config, comic, &ignore, problems));
+        }
+        Ok(site)
+    }
+
+    /// Builds the site into the output folder. Only fails if the templates
+    /// can't be parsed or the output folder can't be written; other problems
+    /// are collected in the report.
+    pub fn build(&self, options: &BuildOptions) -> Result<BuildReport, BuildError> {
+        let dirs = &self.dirs;
+        let mut report = BuildReport {
+            problems: self.problems.clone(),
+            ..BuildReport::default()
+        };
+        // Pages that would be written over each other, or outside the output
+        // folder, stop the build before anything is written.
+        let bad_slug = |problem: &BuildError| matches!(problem, BuildError::BadSlug { .. });
+        if report.problems.iter().any(bad_slug) {
+            return Ok(report);
+        }
+
+        let template_glob = format!("{}/**/*", dirs.templates.display());
+        let mut tera = Tera::new(&template_glob)
+            .map_err(|err| BuildError::template(&dirs.templates, None, &err))?;
+        let templates =
+            hash_folder(&dirs.templates).map_err(|err| BuildError::io(&dirs.templates, err))?;
+        fs::create_dir_all(&dirs.output).map_err(|err| BuildError::io(&dirs.output, err))?;
+        let outputs = Outputs::load(&dirs.output, templates);
+
+        match fs::read_dir(&dirs.static_files) {
+            Ok(files) => {
+                for file in files {
+                    let from = match file {
+                        Ok(file) => file.path(),
+                        Err(err) => {
+                            report
+                                .problems
+                                .push(BuildError::io(&dirs.static_files, err));
+                            continue;
+                        }
+                    };
+                    let to = from
+                        .strip_prefix(&dirs.static_files)
+                        .unwrap()
+                        .display()
+                        .to_string();
+                    copy_file(&from, &to, &outputs, &mut report.problems);
+                }
+            }
+            Err(err) => report
+                .problems
+                .push(BuildError::io(&dirs.static_files, err)),
+        }
+
+        tera.register_function("abs", make_path(self.config.base_path.clone()));
+        tera.register_filter("markdown", markdown_filter);
+
+        let mut context = base_context(&self.config, &self.pages);
+        context.insert("comics", &self.comics);
+
+        if let Err(err) = render(&tera, "index.html", "the index", context, &[""], &outputs) {
+            report.problems.push(err);
+        }
+
+        // Static hosts like GitHub Pages serve 404.html for missing pages, and so
+        // does the server. Older templates folders don't have one.
+        if tera.get_template("404.html").is_ok() {
+            let context = base_context(&self.config, &self.pages);
+            let paths = ["404.html".to_string()];
+            if let Err(err) =
+                render_to(&tera, "404.html", "the 404 page", context, &paths, &outputs)
+            {
+                report.problems.push(err);
+            }
+        }
+
+        let page_problems: Vec<_> = self
+            .pages
+            .par_iter()
+            .map(|page| {
+                let mut context = base_context(&self.config, &self.pages);
+                context.insert("page", page);
+                let name = format!("page {}", &page.title);
+                render(&tera, "page.html", &name, context, &[&page.page], &outputs).err()
+            })
+            .collect();
+        report.problems.extend(page_problems.into_iter().flatten());
+
+        let comic_problems: Vec<_> = self
+            .comics
+            .par_iter()
+            .map(|comic| publish_comic(comic, &tera, &self.config, &self.pages, &outputs))
+            .collect();
+        report.problems.extend(comic_problems.into_iter().flatten());
+
+        outputs
+            .finish(&mut report, options.keep_stale)
+            .map_err(|err| BuildError::io(&dirs.output, err))?;
+        Ok(report)
+    }
+}
+
+/// The text of a page's content, from its content file if it has one.
+fn page_content(root: &Path, page: &ImportPage, problems: &mut Vec<BuildError>) -> String {
+    let file = match &page.content_file {
+        Some(file) => file,
+        None => return page.content.clone(),
+    };
+    let result = join_inside(root, file, "Content file").and_then(|path| {
+        fs::read_to_string(&path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => BuildError::MissingFile {
+                path,
+                what: "Content file".into(),
+            },
+            _ => BuildError::io(&path, err),
+        })
+    });
+    result.unwrap_or_else(|err| {
+        problems.push(err);
+        String::new()
+    })
+}
+
+/// Where a chapter's pages go. Chapters have their own `chapter/` folder in the
+/// comic's, so their names can't be taken for page numbers or `latest`.
+pub(crate) fn chapter_url(comic_url: &str, name: &str) -> String {
+    format!("{}/chapter/{}", comic_url, name)
+}
+
+/// Where thumbnails made from pages go in the output folder. It's outside
+/// `images/`, where the files from the input folder go, so a hand-made
+/// thumbnail can't have the same path as a made one.
+static THUMBNAILS: &str = "thumbnails";
+
+/// Finds the pages and chapters of a comic, or `None` if its folder is
+/// missing.
+fn load_comic(
+    root: &Path,
+    config: &Config,
+    comic: &ImportComic,
+    ignore: &[glob::Pattern],
+    problems: &mut Vec<BuildError>,
+) -> Option<Comic> {
+    let comic_folder = match join_inside(root, &comic.folder, "Comic folder") {
+        Ok(folder) => folder,
+        Err(err) => {
+            problems.push(err);
+            return None;
+        }
+    };
+    if !comic_folder.is_dir() {
+        problems.push(BuildError::MissingFolder {
+            path: comic_folder,
+            what: "Comic folder".into(),
+        });
+        return None;
+    }
+    // Paths inside the comic folder, or `None` if they lead out of it.
+    let mut inside = |path: &Path, what: &str| match join_inside(&comic_folder, path, what) {
+        Ok(path) => Some(path),
+        Err(err) => {
+            problems.push(err);
+            None
+        }
+    };
+    let order: Vec<_> = comic
+        .order
+        .iter()
+        .filter_map(|entry| inside(entry, "Page in the comic's order"))
+        .collect();
+    let chapter_folders: Vec<_> = comic
+        .chapters
+        .iter()
+        .filter_map(|chapter| inside(&chapter.folder, "Chapter folder"))
+        .collect();
+    let thumbnail_page = comic
+        .thumbnail_page
+        .as_ref()
+        .and_then(|page| inside(page, "Thumbnail page"));
+    let thumbnail = comic.thumbnail.as_ref().and_then(|thumbnail| {
+        join_inside(root, thumbnail, "Thumbnail")
+            .map_err(|err| problems.push(err))
+            .ok()
+    });
+    for path in order {
+        if !path.exists() {
+            problems.push(BuildError::MissingFile {
+                path,
+                what: "Page in the comic's order".into(),
+            });
+        }
+    }
+    let (files, folders) = read_folder(root, &comic_folder, ignore, problems);
+    let files = order_paths(files, comic, &comic_folder, problems);
+    let folders = order_paths(folders, comic, &comic_folder, problems);
+    let url = comic.folder.display().to_string();
+    problems.extend(check_chapters(&url, &folders));
+    let mut pages = load_pages(root, files, &config.images, &config.base_path, problems);
+    let mut chapters = Vec::new();
+    for folder in &folders {
+        let name = folder.file_name().unwrap().to_string_lossy().into_owned();
+        let (files, nested) = read_folder(root, folder, ignore, problems);
+        let files = order_paths(files, comic, &comic_folder, problems);
+        for path in nested {
+            problems.push(BuildError::NestedFolder { path });
+        }
+        let mut info = read_chapter_info(folder, problems);
+        if let Some(chapter) = comic.chapters.iter().find(|c| c.folder == Path::new(&name)) {
+            info.title = chapter.title.clone().or(info.title);
+            info.description = chapter.description.clone().or(info.description);
+        }
+        let chapter_pages = load_pages(root, files, &config.images, &config.base_path, problems);
+        pages.extend(chapter_pages.iter().cloned());
+        chapters.push(Chapter {
+            title: info.title.unwrap_or_else(|| name.clone()),
+            description: comic.format.to_html(&info.description.unwrap_or_default()),
+            url: chapter_url(&url, &name),
+            pages: chapter_pages,
+        });
+    }
+    for path in chapter_folders {
+        if !path.is_dir() {
+            problems.push(BuildError::MissingFolder {
+                path,
+                what: "Chapter folder".into(),
+            });
+        }
+    }
+    let (thumbnail, thumbnail_source) = match (thumbnail, thumbnail_page) {
+        (Some(source), _) => (
+            Some(image_path(root, &source)),
+            Some(ThumbnailSource::File(source)),
+        ),
+        (None, Some(source)) => {
+            if !source.is_file() {
+                problems.push(BuildError::MissingFile {
+                    path: source.clone(),
+                    what: "Thumbnail page".into(),
+                });
+            }
+            (
+                Some(format!("{}/{}.jpg", THUMBNAILS, url)),
+                Some(ThumbnailSource::Page(source)),
+            )
+        }
+        (None, None) => match pages.first() {
+            Some(page) => (
+                Some(format!("{}/{}.jpg", THUMBNAILS, url)),
+                Some(ThumbnailSource::Page(page.source.clone())),
+            ),
+            None => (None, None),
+        },
+    };
+    Some(Comic {
+        title: comic.title.clone(),
+        thumbnail,
+        thumbnail_source,
+        url,
+        description: comic.format.to_html(&comic.description),
+        layout: comic.layout,
+        pages,
+        chapters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_folder, BuildOptions};
+
+    #[test]
+    fn sites_dont_read_or_write_outside_their_folders() {
+        let folder = test_folder("outside");
+        let dirs = Dirs {
+            input: folder.join("input"),
+            output: folder.join("output"),
+            config: folder.join("input/config.toml"),
+            templates: folder.join("templates"),
+            static_files: folder.join("static"),
+            errors: folder.join("errors.txt"),
+        };
+        fs::create_dir_all(folder.join("input/comic")).unwrap();
+        fs::create_dir_all(folder.join("secret")).unwrap();
+        fs::write(
+            &dirs.config,
+            r#"
+            title = "Test"
+
+            [[pages]]
+            page = "../escaped"
+            title = "Escaped"
+            content = ""
+
+            [[comics]]
+            folder = "../secret"
+            title = "Secret"
+            description = ""
+
+            [[comics]]
+            folder = "comic"
+            title = "Comic"
+            description = ""
+            thumbnail = "../../thumbnail.png"
+            thumbnail_page = "/etc/hosts"
+            order = ["../../../etc/passwd"]
+            "#,
+        )
+        .unwrap();
+
+        let site = Site::from_dirs(dirs.clone()).unwrap();
+        let outside: Vec<_> = site
+            .problems
+            .iter()
+            .filter_map(|problem| match problem {
+                BuildError::OutsideFolder { what, .. } => Some(&what[..]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            outside,
+            [
+                "Comic folder",
+                "Page in the comic's order",
+                "Thumbnail page",
+                "Thumbnail"
+            ]
+        );
+        assert_eq!(site.comics.len(), 1);
+        assert_eq!(site.comics[0].thumbnail, None);
+
+        let report = site.build(&BuildOptions::default()).unwrap();
+        assert!(report.has_errors());
+        assert!(!report.finished);
+        assert!(!dirs.output.exists());
+        assert!(!folder.join("escaped").exists());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}